@@ -0,0 +1,408 @@
+use std::{fs, path::Path};
+
+use anyhow::Context;
+
+use crate::{
+    hittable::{HitRecord, Hittable, HittableObject, AABB},
+    ray::Ray,
+    Error, Vec3,
+};
+
+/// A bicubic Bézier patch defined by a 4 × 4 grid of control points, e.g. a part of the Utah
+/// teapot. Rays are intersected directly by subdividing the patch down to small pieces, which
+/// are refined by Newton's method. The surface coordinates are the parameters of the patch, the
+/// outward normal is the cross product of the derivatives along u and v.
+pub struct BezierPatch {
+    /// The control points in row-major order, the columns run along u, the rows along v.
+    pub control: [Vec3; 16],
+}
+
+impl BezierPatch {
+    /// Construct a patch from its control points in row-major order, the columns run along u,
+    /// the rows along v.
+    pub fn new(control: [Vec3; 16]) -> Self {
+        Self { control }
+    }
+
+    /// Load all patches from a file in the classic format of the Utah teapot: the number of
+    /// patches, 16 one-based indices of control points for each patch, the number of control
+    /// points and the coordinates of each control point. Numbers are separated by commas or
+    /// whitespace.
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<Self>> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text).with_context(|| format!("Loading {}", path.display()))
+    }
+
+    /// Parse patches in the format described in [load](BezierPatch::load).
+    pub fn parse(text: &str) -> anyhow::Result<Vec<Self>> {
+        let mut tokens = text
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|token| !token.is_empty());
+        let mut next = |what: &str| {
+            tokens
+                .next()
+                .ok_or(Error::MalformedPatchFile)
+                .with_context(|| format!("Missing {}", what))
+        };
+
+        let patch_count: usize = next("number of patches")?.parse()?;
+        let mut indices = Vec::with_capacity(patch_count);
+        for _ in 0..patch_count {
+            let mut patch = [0usize; 16];
+            for index in patch.iter_mut() {
+                *index = next("control point index")?.parse()?;
+            }
+            indices.push(patch);
+        }
+
+        let point_count: usize = next("number of control points")?.parse()?;
+        let mut points = Vec::with_capacity(point_count);
+        for _ in 0..point_count {
+            let mut coordinates = [0.0; 3];
+            for coordinate in coordinates.iter_mut() {
+                *coordinate = next("control point coordinate")?.parse()?;
+            }
+            let [x, y, z] = coordinates;
+            points.push(Vec3::new(x, y, z));
+        }
+
+        indices
+            .iter()
+            .map(|patch| {
+                let mut control = [Vec3::origin(); 16];
+                for (point, &index) in control.iter_mut().zip(patch) {
+                    *point = *index
+                        .checked_sub(1)
+                        .and_then(|index| points.get(index))
+                        .ok_or(Error::MalformedPatchFile)
+                        .with_context(|| format!("Control point {} out of range", index))?;
+                }
+                Ok(Self::new(control))
+            })
+            .collect()
+    }
+
+    /// The point and its derivatives along u and v at the given parameters.
+    fn evaluate(&self, u: f64, v: f64) -> (Vec3, Vec3, Vec3) {
+        let (bu, du) = (bernstein(u), bernstein_derivative(u));
+        let (bv, dv) = (bernstein(v), bernstein_derivative(v));
+
+        let mut point = Vec3::origin();
+        let mut along_u = Vec3::origin();
+        let mut along_v = Vec3::origin();
+        for row in 0..4 {
+            for column in 0..4 {
+                let control = self.control[4 * row + column];
+                point += bu[column] * bv[row] * control;
+                along_u += du[column] * bv[row] * control;
+                along_v += bu[column] * dv[row] * control;
+            }
+        }
+
+        (point, along_u, along_v)
+    }
+
+    /// The outward normal at the given parameters. Rows or columns of coinciding control points,
+    /// e.g. at the top of the teapot lid, make the derivatives vanish, the normal is then taken
+    /// slightly towards the middle of the patch.
+    fn normal(&self, u: f64, v: f64) -> Option<Vec3> {
+        for &nudge in &[0.0, 1e-6, 1e-3] {
+            let (_, along_u, along_v) = self.evaluate(u + (0.5 - u) * nudge, v + (0.5 - v) * nudge);
+            let normal = along_u.cross(along_v);
+            if normal.norm_squared() > 1e-24 {
+                return Some(normal.normalized());
+            }
+        }
+
+        None
+    }
+
+    /// Find where the ray hits the patch by Newton's method, starting from the given parameters.
+    fn newton(&self, ray: &Ray, (mut u, mut v): (f64, f64)) -> Option<(f64, f64, f64)> {
+        const MAX_ITERATIONS: usize = 20;
+
+        let (o, d) = (ray.origin(), ray.direction());
+        let (point, _, _) = self.evaluate(u, v);
+        let mut t = (point - o).dot(d) / d.norm_squared();
+
+        for _ in 0..MAX_ITERATIONS {
+            let (point, along_u, along_v) = self.evaluate(u, v);
+            let residual = point - ray.at(t);
+            if residual.norm() < 1e-10 * point.norm().max(1.0) {
+                return Some((t, u, v));
+            }
+
+            // solve [along_u, along_v, -d] (du, dv, dt) = -residual by Cramer's rule
+            let determinant = along_u.dot(along_v.cross(-d));
+            if determinant.abs() < 1e-18 {
+                return None;
+            }
+            u -= residual.dot(along_v.cross(-d)) / determinant;
+            v -= along_u.dot(residual.cross(-d)) / determinant;
+            t -= along_u.dot(along_v.cross(residual)) / determinant;
+        }
+
+        None
+    }
+}
+
+impl Hittable for BezierPatch {
+    fn hit(&self, ray: &Ray, t_min: f64, mut t_max: f64) -> Option<HitRecord> {
+        // pieces are small enough for Newton's method to converge after this many subdivisions
+        const MAX_DEPTH: usize = 5;
+        const SLACK: f64 = 1e-9;
+
+        let mut closest = None;
+        let mut pieces = vec![Piece {
+            control: self.control,
+            u: (0.0, 1.0),
+            v: (0.0, 1.0),
+            depth: 0,
+        }];
+
+        while let Some(piece) = pieces.pop() {
+            // the patch lies within the convex hull of its control points
+            if AABB::containing(&piece.control)
+                .interval(ray, t_min, t_max)
+                .is_none()
+            {
+                continue;
+            }
+
+            if piece.depth < MAX_DEPTH {
+                pieces.extend(piece.split());
+                continue;
+            }
+
+            let start = (0.5 * (piece.u.0 + piece.u.1), 0.5 * (piece.v.0 + piece.v.1));
+            let (t, u, v) = match self.newton(ray, start) {
+                Some(hit) => hit,
+                None => continue,
+            };
+            let inside = |x: f64| (-SLACK..=1.0 + SLACK).contains(&x);
+            if t < t_min || t > t_max || !inside(u) || !inside(v) {
+                continue;
+            }
+
+            let (u, v) = (u.clamp(0.0, 1.0), v.clamp(0.0, 1.0));
+            let normal = match self.normal(u, v) {
+                Some(normal) => normal,
+                None => continue,
+            };
+            let (_, along_u, _) = self.evaluate(u, v);
+
+            t_max = t;
+            closest = Some(HitRecord::new(ray, t, normal, (u, v), along_u));
+        }
+
+        closest
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        Some(AABB::containing(&self.control))
+    }
+}
+
+impl From<BezierPatch> for HittableObject {
+    fn from(patch: BezierPatch) -> Self {
+        Self::Object(Box::new(patch))
+    }
+}
+
+/// A part of a patch over a range of its parameters, described by its own control points.
+struct Piece {
+    control: [Vec3; 16],
+    u: (f64, f64),
+    v: (f64, f64),
+    depth: usize,
+}
+
+impl Piece {
+    /// Split the piece into quarters at the middle of both parameter ranges.
+    fn split(&self) -> [Piece; 4] {
+        let (left, right) = split_columns(&self.control);
+        let (u0, u1) = self.u;
+        let (v0, v1) = self.v;
+        let (u_mid, v_mid) = (0.5 * (u0 + u1), 0.5 * (v0 + v1));
+
+        let quarter = |control: [Vec3; 16], u: (f64, f64), v: (f64, f64)| Piece {
+            control,
+            u,
+            v,
+            depth: self.depth + 1,
+        };
+        let (bottom_left, top_left) = split_rows(&left);
+        let (bottom_right, top_right) = split_rows(&right);
+
+        [
+            quarter(bottom_left, (u0, u_mid), (v0, v_mid)),
+            quarter(top_left, (u0, u_mid), (v_mid, v1)),
+            quarter(bottom_right, (u_mid, u1), (v0, v_mid)),
+            quarter(top_right, (u_mid, u1), (v_mid, v1)),
+        ]
+    }
+}
+
+/// Split a patch at u = 0.5, each row is split by de Casteljau's algorithm.
+fn split_columns(control: &[Vec3; 16]) -> ([Vec3; 16], [Vec3; 16]) {
+    let mut left = [Vec3::origin(); 16];
+    let mut right = [Vec3::origin(); 16];
+
+    for row in 0..4 {
+        let curve = [0, 1, 2, 3].map(|column| control[4 * row + column]);
+        let (l, r) = split_curve(curve);
+        for column in 0..4 {
+            left[4 * row + column] = l[column];
+            right[4 * row + column] = r[column];
+        }
+    }
+
+    (left, right)
+}
+
+/// Split a patch at v = 0.5, each column is split by de Casteljau's algorithm.
+fn split_rows(control: &[Vec3; 16]) -> ([Vec3; 16], [Vec3; 16]) {
+    let mut bottom = [Vec3::origin(); 16];
+    let mut top = [Vec3::origin(); 16];
+
+    for column in 0..4 {
+        let curve = [0, 1, 2, 3].map(|row| control[4 * row + column]);
+        let (b, t) = split_curve(curve);
+        for row in 0..4 {
+            bottom[4 * row + column] = b[row];
+            top[4 * row + column] = t[row];
+        }
+    }
+
+    (bottom, top)
+}
+
+/// Split a cubic Bézier curve at its middle into the control points of both halves.
+fn split_curve([p0, p1, p2, p3]: [Vec3; 4]) -> ([Vec3; 4], [Vec3; 4]) {
+    let mid = |a: Vec3, b: Vec3| 0.5 * (a + b);
+
+    let (q0, q1, q2) = (mid(p0, p1), mid(p1, p2), mid(p2, p3));
+    let (r0, r1) = (mid(q0, q1), mid(q1, q2));
+    let s = mid(r0, r1);
+
+    ([p0, q0, r0, s], [s, r1, q2, p3])
+}
+
+/// The cubic Bernstein polynomials at t.
+fn bernstein(t: f64) -> [f64; 4] {
+    let s = 1.0 - t;
+    [s * s * s, 3.0 * t * s * s, 3.0 * t * t * s, t * t * t]
+}
+
+/// The derivatives of the cubic Bernstein polynomials at t.
+fn bernstein_derivative(t: f64) -> [f64; 4] {
+    let s = 1.0 - t;
+    [
+        -3.0 * s * s,
+        3.0 * s * s - 6.0 * t * s,
+        6.0 * t * s - 3.0 * t * t,
+        3.0 * t * t,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::{Pointing, Sphere};
+
+    /// A patch over the unit square bulging up to the height z = 1 in the middle.
+    fn dome() -> BezierPatch {
+        let mut control = [Vec3::origin(); 16];
+        for row in 0..4 {
+            for column in 0..4 {
+                let inner = |i: usize| i == 1 || i == 2;
+                let z = if inner(row) && inner(column) {
+                    16.0 / 9.0
+                } else {
+                    0.0
+                };
+                control[4 * row + column] = Vec3::new(column as f64 / 3.0, row as f64 / 3.0, z);
+            }
+        }
+        BezierPatch::new(control)
+    }
+
+    #[test]
+    fn flat_patch_hit() {
+        let mut control = [Vec3::origin(); 16];
+        for (i, point) in control.iter_mut().enumerate() {
+            *point = Vec3::new((i % 4) as f64 / 3.0, (i / 4) as f64 / 3.0, 0.0);
+        }
+        let square = BezierPatch::new(control);
+
+        let ray = Ray::new(Vec3::new(0.25, 0.75, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let record = square.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!((record.t - 1.0).abs() < 1e-9);
+        assert!((record.u - 0.25).abs() < 1e-9 && (record.v - 0.75).abs() < 1e-9);
+        assert!((record.normal - Vec3::new(0.0, 0.0, 1.0)).norm() < 1e-9);
+        assert_eq!(record.pointing, Pointing::Outward);
+
+        let miss = Ray::new(Vec3::new(1.25, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(square.hit(&miss, 0.0, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn curved_patch_hit() {
+        let mut rng = rand::thread_rng();
+        let dome = dome();
+        let aabb = dome.bounding_box().unwrap();
+
+        // the middle of the dome is at height 1
+        let down = Ray::new(Vec3::new(0.5, 0.5, 3.0), Vec3::new(0.0, 0.0, -1.0));
+        let record = dome.hit(&down, 0.0, f64::INFINITY).unwrap();
+        assert!((record.t - 2.0).abs() < 1e-9);
+
+        for _ in 0..100 {
+            let origin =
+                Vec3::new(0.5, 0.5, 0.5) + 3.0 * Sphere::unit().random_point_on_surface(&mut rng);
+            let target =
+                Vec3::new(0.5, 0.5, 0.0) + 0.5 * Sphere::unit().random_point_in_sphere(&mut rng);
+            let ray = Ray::new(origin, target - origin);
+
+            if let Some(record) = dome.hit(&ray, 0.0, f64::INFINITY) {
+                let (point, _, _) = dome.evaluate(record.u, record.v);
+                assert!((point - record.hit_at).norm() < 1e-9);
+                assert!((record.normal.norm() - 1.0).abs() < 1e-9);
+                assert!(aabb.hit(&ray, 0.0, f64::INFINITY));
+
+                // no closer hit is missed
+                assert!(dome.hit(&ray, 0.0, record.t - 1e-6).is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn parse_patches() {
+        // two patches sharing their control points in reverse order
+        let mut text = String::from("2\n");
+        text += &(1..=16)
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        text += "\n";
+        text += &(1..=16)
+            .rev()
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        text += "\n16\n";
+        for i in 0..16 {
+            text += &format!("{},{},{}\n", i % 4, i / 4, 0.5 * i as f64);
+        }
+
+        let patches = BezierPatch::parse(&text).unwrap();
+        assert_eq!(patches.len(), 2);
+        assert_eq!(patches[0].control[5], Vec3::new(1.0, 1.0, 2.5));
+        assert_eq!(patches[1].control[0], patches[0].control[15]);
+
+        assert!(BezierPatch::parse("1\n1,2,3").is_err());
+        let out_of_range = text.replacen("1,2,", "0,2,", 1);
+        assert!(BezierPatch::parse(&out_of_range).is_err());
+    }
+}
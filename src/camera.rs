@@ -128,7 +128,6 @@ impl CameraBuilder {
             lens_radius,
             u,
             v,
-            w,
         }
     }
 }
@@ -142,7 +141,6 @@ pub struct Camera {
     lens_radius: f64,
     u: Vec3,
     v: Vec3,
-    w: Vec3,
 }
 
 impl Camera {
@@ -178,7 +176,7 @@ impl Camera {
     }
 
     /// Scan the image pixel by pixel, row by row from bottom to top.
-    pub fn cast(&self, pixel_width: u32, pixel_height: u32) -> RayCaster {
+    pub fn cast(&self, pixel_width: u32, pixel_height: u32) -> RayCaster<'_> {
         RayCaster::new(self, pixel_width, pixel_height)
     }
 }
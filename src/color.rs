@@ -31,6 +31,16 @@ impl Rgb {
         self[2]
     }
 
+    /// Relative luminance of the color with Rec. 709 primaries.
+    pub fn luminance(self) -> f64 {
+        0.2126 * self.r() + 0.7152 * self.g() + 0.0722 * self.b()
+    }
+
+    /// Value of the brightest channel.
+    pub fn max_channel(self) -> f64 {
+        self.r().max(self.g()).max(self.b())
+    }
+
     /// Return a color whose channels all have been clamped to the valid range.
     pub fn clamp(self) -> Self {
         Self::new(
@@ -76,8 +86,9 @@ impl RgbAccumulator {
 
     /// Sample a reasonably representative color based on all the feeded colors.
     pub fn sample(&self) -> Rgb {
-        let rgb = self.sum / (self.len as f64);
-        Rgb::new(rgb.r().sqrt(), rgb.g().sqrt(), rgb.b().sqrt()).clamp()
+        // channels may be negative when colors of single wavelengths are out of the sRGB gamut
+        let rgb = (self.sum / (self.len as f64)).clamp();
+        Rgb::new(rgb.r().sqrt(), rgb.g().sqrt(), rgb.b().sqrt())
     }
 }
 
@@ -0,0 +1,224 @@
+use crate::{
+    hittable::{HitRecord, Hittable, HittableObject, Pointing, AABB},
+    ray::Ray,
+};
+
+/// How the volumes of the two objects of a [Csg](Csg) node are combined.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operation {
+    /// Points inside of either object.
+    Union,
+    /// Points inside of both objects.
+    Intersection,
+    /// Points inside of the left object but outside of the right one.
+    Difference,
+}
+
+impl Operation {
+    fn inside(self, left: bool, right: bool) -> bool {
+        match self {
+            Operation::Union => left || right,
+            Operation::Intersection => left && right,
+            Operation::Difference => left && !right,
+        }
+    }
+}
+
+/// A boolean combination of the volumes of two closed objects, e.g. a pipe fitting drilled out of
+/// a cylinder. Objects must be closed, so rays alternate between entering and leaving them.
+/// Surfaces of the right object carved out of the left object by a difference face inwards.
+pub struct Csg {
+    operation: Operation,
+    left: HittableObject,
+    right: HittableObject,
+}
+
+impl Csg {
+    /// Combine the volumes of two closed objects by the given operation.
+    pub fn new<L, R>(operation: Operation, left: L, right: R) -> Self
+    where
+        L: Into<HittableObject>,
+        R: Into<HittableObject>,
+    {
+        Self {
+            operation,
+            left: left.into(),
+            right: right.into(),
+        }
+    }
+
+    /// The volume inside of either object.
+    pub fn union<L: Into<HittableObject>, R: Into<HittableObject>>(left: L, right: R) -> Self {
+        Self::new(Operation::Union, left, right)
+    }
+
+    /// The volume inside of both objects.
+    pub fn intersection<L: Into<HittableObject>, R: Into<HittableObject>>(
+        left: L,
+        right: R,
+    ) -> Self {
+        Self::new(Operation::Intersection, left, right)
+    }
+
+    /// The volume inside of the left object but outside of the right one.
+    pub fn difference<L: Into<HittableObject>, R: Into<HittableObject>>(left: L, right: R) -> Self {
+        Self::new(Operation::Difference, left, right)
+    }
+}
+
+impl Hittable for Csg {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.intersections(ray, t_min, t_max).into_iter().next()
+    }
+
+    fn intersections(&self, ray: &Ray, t_min: f64, t_max: f64) -> Vec<HitRecord> {
+        // whether the ray starts inside of an object is decided by the first hit, so the
+        // intervals are traced to infinity even if only a part of the range is asked for
+        let left = self.left.intersections(ray, t_min, f64::INFINITY);
+        let right = self.right.intersections(ray, t_min, f64::INFINITY);
+        let starts_inside = |records: &[HitRecord]| {
+            records
+                .first()
+                .is_some_and(|record| record.pointing == Pointing::Inward)
+        };
+
+        let mut inside_left = starts_inside(&left);
+        let mut inside_right = starts_inside(&right);
+        let mut inside = self.operation.inside(inside_left, inside_right);
+
+        let mut left = left.into_iter().peekable();
+        let mut right = right.into_iter().peekable();
+        let mut records = vec![];
+
+        loop {
+            let from_left = match (left.peek(), right.peek()) {
+                (Some(l), Some(r)) => l.t <= r.t,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break,
+            };
+
+            let mut record = if from_left {
+                inside_left = !inside_left;
+                left.next().unwrap()
+            } else {
+                inside_right = !inside_right;
+                right.next().unwrap()
+            };
+
+            if record.t > t_max {
+                break;
+            }
+
+            // only hits where the ray enters or leaves the combined volume are on its surface
+            let was_inside = inside;
+            inside = self.operation.inside(inside_left, inside_right);
+            if inside == was_inside {
+                continue;
+            }
+
+            // the pointing must agree with the ray entering or leaving the combined volume,
+            // which flips the surfaces carved out by differences
+            let pointing = if inside {
+                Pointing::Outward
+            } else {
+                Pointing::Inward
+            };
+            record.pointing = pointing;
+            records.push(record);
+        }
+
+        records
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        let left = self.left.bounding_box();
+        let right = self.right.bounding_box();
+
+        match self.operation {
+            Operation::Union => Some(left?.merge(&right?)),
+            Operation::Intersection => left.or(right),
+            Operation::Difference => left,
+        }
+    }
+}
+
+impl From<Csg> for HittableObject {
+    fn from(csg: Csg) -> Self {
+        Self::Object(Box::new(csg))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hittable::Sphere, Vec3};
+
+    fn spheres() -> (Sphere, Sphere) {
+        let sphere = |x: f64| Sphere {
+            center: Vec3::new(x, 0.0, 0.0),
+            radius: 1.0,
+        };
+        (sphere(-0.5), sphere(0.5))
+    }
+
+    /// The ray parameters and pointings of all hits of the ray along the x axis.
+    fn hits(csg: &Csg, origin: f64) -> Vec<(f64, Pointing)> {
+        let ray = Ray::new(Vec3::new(origin, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        csg.intersections(&ray, 0.0, f64::INFINITY)
+            .iter()
+            .map(|record| (record.t + origin, record.pointing))
+            .collect()
+    }
+
+    fn assert_hits(actual: &[(f64, Pointing)], expected: &[(f64, Pointing)]) {
+        assert_eq!(actual.len(), expected.len());
+        for ((t, pointing), (expected_t, expected_pointing)) in actual.iter().zip(expected) {
+            assert!((t - expected_t).abs() < 1e-9, "{} {}", t, expected_t);
+            assert_eq!(pointing, expected_pointing);
+        }
+    }
+
+    #[test]
+    fn csg_operations() {
+        use Pointing::*;
+
+        let (a, b) = spheres();
+        let union = Csg::union(a, b);
+        assert_hits(&hits(&union, -3.0), &[(-1.5, Outward), (1.5, Inward)]);
+
+        let (a, b) = spheres();
+        let lens = Csg::intersection(a, b);
+        assert_hits(&hits(&lens, -3.0), &[(-0.5, Outward), (0.5, Inward)]);
+        // starting inside of the lens
+        assert_hits(&hits(&lens, 0.0), &[(0.5, Inward)]);
+
+        let (a, b) = spheres();
+        let bitten = Csg::difference(a, b);
+        assert_hits(&hits(&bitten, -3.0), &[(-1.5, Outward), (-0.5, Inward)]);
+        // the carved surface is entered from the outside
+        let backwards = Ray::new(Vec3::new(3.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        let record = bitten.hit(&backwards, 0.0, f64::INFINITY).unwrap();
+        assert!((record.t - 3.5).abs() < 1e-9);
+        assert_eq!(record.pointing, Outward);
+        assert!((record.normal - Vec3::new(1.0, 0.0, 0.0)).norm() < 1e-9);
+
+        // nested nodes, a hole drilled through the union
+        let (a, b) = spheres();
+        let drill = Sphere {
+            center: Vec3::new(0.0, 0.0, 0.0),
+            radius: 0.25,
+        };
+        let drilled = Csg::difference(Csg::union(a, b), drill);
+        assert_hits(
+            &hits(&drilled, -3.0),
+            &[
+                (-1.5, Outward),
+                (-0.25, Inward),
+                (0.25, Outward),
+                (1.5, Inward),
+            ],
+        );
+        assert!(drilled.bounding_box().is_some());
+    }
+}
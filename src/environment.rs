@@ -0,0 +1,271 @@
+use std::{f64::consts::PI, fs::File, io::BufReader, path::Path};
+
+use anyhow::Context;
+use image::codecs::hdr::HdrDecoder;
+use rand::Rng;
+
+use crate::{
+    color::{self, Rgb},
+    light::LightSample,
+    sampling::Distribution2D,
+    sky::Sky,
+    Vec3,
+};
+
+/// What a ray sees when it escapes the world.
+#[derive(Default)]
+pub enum Background {
+    /// A white to light blue gradient from the bottom to the top.
+    #[default]
+    Gradient,
+    /// An environment light looked up from an equirectangular image.
+    Environment(EnvironmentMap),
+    /// An analytic daylight sky.
+    Sky(Sky),
+}
+
+impl Background {
+    /// The radiance arriving from the given direction.
+    pub fn radiance(&self, direction: Vec3) -> Rgb {
+        match self {
+            Background::Gradient => {
+                let unit_dir = direction.normalized();
+                let t = 0.5 * (unit_dir.y() + 1.0);
+                (1.0 - t) * color::WHITE + t * color::LIGHTBLUE
+            }
+            Background::Environment(map) => map.radiance(direction),
+            Background::Sky(sky) => sky.radiance(direction),
+        }
+    }
+
+    /// Sample a direction the background may be seen from, return the sampled light and the PDF
+    /// with respect to solid angle of sampling its direction. Return `None` if the background
+    /// doesn't support importance sampling.
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> Option<(LightSample, f64)> {
+        match self {
+            Background::Gradient | Background::Sky(_) => None,
+            Background::Environment(map) => map.sample(rng),
+        }
+    }
+
+    /// The PDF with respect to solid angle of [sample](Background::sample) sampling the given
+    /// direction, always zero if the background doesn't support importance sampling.
+    pub fn pdf(&self, direction: Vec3) -> f64 {
+        match self {
+            Background::Gradient | Background::Sky(_) => 0.0,
+            Background::Environment(map) => map.pdf(direction),
+        }
+    }
+}
+
+impl From<EnvironmentMap> for Background {
+    fn from(map: EnvironmentMap) -> Self {
+        Self::Environment(map)
+    }
+}
+
+impl From<Sky> for Background {
+    fn from(sky: Sky) -> Self {
+        Self::Sky(sky)
+    }
+}
+
+/// An environment light looked up from an equirectangular image, the top row of the image is
+/// straight up (+y), the center of the image looks towards -z.
+///
+/// Directions are importance sampled proportional to the luminance of the pixels.
+pub struct EnvironmentMap {
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgb>,
+    rotation: f64,
+    intensity: f64,
+    distribution: Distribution2D,
+}
+
+impl EnvironmentMap {
+    /// Load an environment map from a Radiance HDR (.hdr) file.
+    pub fn open<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open environment map {}", path.display()))?;
+        let decoder =
+            HdrDecoder::new(BufReader::new(file)).context("Failed to decode HDR header")?;
+        let metadata = decoder.metadata();
+
+        let pixels = decoder
+            .read_image_hdr()
+            .context("Failed to decode HDR pixels")?
+            .into_iter()
+            .map(|p| {
+                let [r, g, b] = p.0;
+                Rgb::new(r as f64, g as f64, b as f64)
+            })
+            .collect();
+
+        Ok(Self::from_pixels(
+            metadata.width as usize,
+            metadata.height as usize,
+            pixels,
+        ))
+    }
+
+    /// Construct an environment map from linear radiance values in row-major order, top to
+    /// bottom for rows.
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<Rgb>) -> Self {
+        assert!(width > 0);
+        assert!(height > 0);
+        assert_eq!(pixels.len(), width * height);
+
+        // rows close to the poles cover a smaller solid angle
+        let func: Vec<_> = pixels
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let theta = PI * ((i / width) as f64 + 0.5) / height as f64;
+                p.luminance() * theta.sin()
+            })
+            .collect();
+        let distribution = Distribution2D::new(&func, width, height);
+
+        Self {
+            width,
+            height,
+            pixels,
+            rotation: 0.0,
+            intensity: 1.0,
+            distribution,
+        }
+    }
+
+    /// Set the rotation of the environment map around the +y axis in degrees.
+    ///
+    /// # Default:
+    /// 0 degrees
+    pub fn rotation(&mut self, rotation: f64) -> &mut Self {
+        self.rotation = rotation.to_radians();
+        self
+    }
+
+    /// Set the multiplier applied to all radiance values.
+    ///
+    /// # Default:
+    /// 1.0
+    pub fn intensity(&mut self, intensity: f64) -> &mut Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// The radiance arriving from the given direction.
+    pub fn radiance(&self, direction: Vec3) -> Rgb {
+        let (u, v) = self.direction_to_uv(direction);
+        let x = ((u * self.width as f64) as usize).min(self.width - 1);
+        let y = ((v * self.height as f64) as usize).min(self.height - 1);
+        self.intensity * self.pixels[y * self.width + x]
+    }
+
+    fn sample<R: Rng>(&self, rng: &mut R) -> Option<(LightSample, f64)> {
+        let ((u, v), pdf_uv) = self.distribution.sample(rng.gen(), rng.gen());
+        if pdf_uv <= 0.0 {
+            return None;
+        }
+
+        let (direction, sin_theta) = self.uv_to_direction(u, v);
+        if sin_theta <= 0.0 {
+            return None;
+        }
+
+        let sample = LightSample {
+            direction,
+            distance: f64::INFINITY,
+            radiance: self.radiance(direction),
+        };
+
+        Some((sample, pdf_uv / (2.0 * PI * PI * sin_theta)))
+    }
+
+    fn pdf(&self, direction: Vec3) -> f64 {
+        let (u, v) = self.direction_to_uv(direction);
+        let sin_theta = (PI * v).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+
+        self.distribution.pdf(u, v) / (2.0 * PI * PI * sin_theta)
+    }
+
+    fn direction_to_uv(&self, direction: Vec3) -> (f64, f64) {
+        let d = direction.normalized();
+        let theta = d.y().clamp(-1.0, 1.0).acos();
+        let phi = d.x().atan2(-d.z()) - self.rotation;
+        let u = (phi / (2.0 * PI) + 0.5).rem_euclid(1.0);
+        (u, theta / PI)
+    }
+
+    fn uv_to_direction(&self, u: f64, v: f64) -> (Vec3, f64) {
+        let theta = PI * v;
+        let phi = 2.0 * PI * (u - 0.5) + self.rotation;
+        let sin_theta = theta.sin();
+        let direction = Vec3::new(sin_theta * phi.sin(), theta.cos(), -sin_theta * phi.cos());
+        (direction, sin_theta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Sphere;
+
+    fn checker_map() -> EnvironmentMap {
+        const WIDTH: usize = 16;
+        const HEIGHT: usize = 8;
+
+        let pixels = (0..WIDTH * HEIGHT)
+            .map(|i| {
+                if (i % WIDTH + i / WIDTH) % 2 == 1 {
+                    Rgb::new(0.1, 0.1, 0.1)
+                } else {
+                    Rgb::new(4.0, 2.0, 1.0)
+                }
+            })
+            .collect();
+
+        let mut map = EnvironmentMap::from_pixels(WIDTH, HEIGHT, pixels);
+        map.rotation(30.0).intensity(2.0);
+        map
+    }
+
+    #[test]
+    fn environment_uv_round_trip() {
+        let map = checker_map();
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..100 {
+            let direction = Sphere::unit().random_point_on_surface(&mut rng);
+            let (u, v) = map.direction_to_uv(direction);
+            let (round_trip, _) = map.uv_to_direction(u, v);
+            assert!((round_trip - direction.normalized()).norm() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn environment_sample_pdf() {
+        let map = checker_map();
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..100 {
+            let (sample, pdf) = map.sample(&mut rng).unwrap();
+            assert!((map.pdf(sample.direction) - pdf).abs() < 1e-6 * pdf);
+        }
+
+        // the PDF integrates to 1 over the unit sphere
+        const N: usize = 100_000;
+        let integral = (0..N)
+            .map(|_| map.pdf(Sphere::unit().random_point_on_surface(&mut rng)))
+            .sum::<f64>()
+            * 4.0
+            * PI
+            / N as f64;
+        assert!((integral - 1.0).abs() < 0.05, "integral {}", integral);
+    }
+}
@@ -0,0 +1,330 @@
+use std::path::Path;
+
+use crate::{
+    hittable::{HitRecord, Hittable, HittableObject, AABB},
+    ray::Ray,
+    Vec3,
+};
+
+/// Terrain described by a grid of heights above the xz plane, e.g. loaded from a grayscale
+/// heightmap. Each grid cell is split into two triangles, shading normals are interpolated from
+/// the slopes at the grid points. The surface coordinates match the ones of an
+/// [ImageTexture](crate::texture::ImageTexture) of the same image.
+pub struct Heightfield {
+    corner: Vec3,
+    size: Vec3,
+    width: usize,
+    depth: usize,
+    heights: Vec<f64>,
+    normals: Vec<Vec3>,
+}
+
+impl Heightfield {
+    /// Load a heightmap from an image file, black is the bottom and white is the top of the box
+    /// of the given size at the corner. Columns of the image run along x, rows along z.
+    pub fn open<P: AsRef<Path>>(path: P, corner: Vec3, size: Vec3) -> anyhow::Result<Self> {
+        let image = image::open(path)?.to_luma16();
+        let (width, depth) = image.dimensions();
+        let heights = image
+            .pixels()
+            .map(|pixel| pixel.0[0] as f64 / u16::MAX as f64)
+            .collect();
+
+        Ok(Self::from_heights(
+            width as usize,
+            depth as usize,
+            heights,
+            corner,
+            size,
+        ))
+    }
+
+    /// Construct a heightfield from heights in [0, 1] in row-major order, scaled to the box of the
+    /// given size at the corner.
+    pub fn from_heights(
+        width: usize,
+        depth: usize,
+        heights: Vec<f64>,
+        corner: Vec3,
+        size: Vec3,
+    ) -> Self {
+        assert!(width >= 2 && depth >= 2);
+        assert_eq!(heights.len(), width * depth);
+
+        let mut heightfield = Self {
+            corner,
+            size,
+            width,
+            depth,
+            heights,
+            normals: vec![],
+        };
+        heightfield.normals = (0..width * depth)
+            .map(|index| heightfield.vertex_normal(index % width, index / width))
+            .collect();
+
+        heightfield
+    }
+
+    /// The spacing of grid points along x and z.
+    fn spacing(&self) -> (f64, f64) {
+        (
+            self.size.x() / (self.width - 1) as f64,
+            self.size.z() / (self.depth - 1) as f64,
+        )
+    }
+
+    fn vertex(&self, i: usize, j: usize) -> Vec3 {
+        let (dx, dz) = self.spacing();
+        let height = self.heights[j * self.width + i] * self.size.y();
+        self.corner + Vec3::new(i as f64 * dx, height, j as f64 * dz)
+    }
+
+    /// The normal at a grid point by central differences of the neighboring heights, one-sided at
+    /// the borders.
+    fn vertex_normal(&self, i: usize, j: usize) -> Vec3 {
+        let (i0, i1) = (i.saturating_sub(1), (i + 1).min(self.width - 1));
+        let (j0, j1) = (j.saturating_sub(1), (j + 1).min(self.depth - 1));
+
+        let along_x = self.vertex(i1, j) - self.vertex(i0, j);
+        let along_z = self.vertex(i, j1) - self.vertex(i, j0);
+        along_z.cross(along_x).normalized()
+    }
+
+    /// Hit the two triangles of the grid cell with the lower corner at the given grid point.
+    fn hit_cell(&self, ray: &Ray, i: usize, j: usize, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)];
+        let triangles = [[0, 1, 2], [0, 2, 3]];
+
+        let mut closest: Option<HitRecord> = None;
+        let mut t_max = t_max;
+        for triangle in &triangles {
+            let [a, b, c] = triangle.map(|k| corners[k]);
+            let vertices = [a, b, c].map(|(i, j)| self.vertex(i, j));
+            let (t, b1, b2) = match hit_triangle(ray, vertices, t_min, t_max) {
+                Some(hit) => hit,
+                None => continue,
+            };
+
+            // the triangles of the cell face upwards
+            let mut normal = (vertices[2] - vertices[0])
+                .cross(vertices[1] - vertices[0])
+                .normalized();
+            if normal.y() < 0.0 {
+                normal = -normal;
+            }
+
+            let p = ray.at(t) - self.corner;
+            let uv = (p.x() / self.size.x(), 1.0 - p.z() / self.size.z());
+            let record = HitRecord::new(ray, t, normal, uv, Vec3::new(1.0, 0.0, 0.0));
+
+            let [na, nb, nc] = [a, b, c].map(|(i, j)| self.normals[j * self.width + i]);
+            let shading = (1.0 - b1 - b2) * na + b1 * nb + b2 * nc;
+            let shading = if shading.dot(record.geometric_normal) < 0.0 {
+                -shading
+            } else {
+                shading
+            };
+
+            t_max = t;
+            closest = Some(record.with_shading_normal(shading));
+        }
+
+        closest
+    }
+}
+
+impl Hittable for Heightfield {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let aabb = self.bounding_box()?;
+        let (t_enter, t_exit) = aabb.interval(ray, t_min, t_max)?;
+
+        let (dx, dz) = self.spacing();
+        let (cells_x, cells_z) = (self.width - 1, self.depth - 1);
+        let start = ray.at(t_enter) - self.corner;
+        let d = ray.direction();
+
+        // the cell the ray enters the box in, clamped as the entry point is on the border
+        let cell = |offset: f64, spacing: f64, cells: usize| {
+            ((offset / spacing).floor().max(0.0) as usize).min(cells - 1)
+        };
+        let (mut i, mut j) = (cell(start.x(), dx, cells_x), cell(start.z(), dz, cells_z));
+
+        // the ray parameter where the ray crosses the next cell border along an axis, and how far
+        // apart the crossings are
+        let crossing = |index: usize, spacing: f64, origin: f64, direction: f64| {
+            if direction == 0.0 {
+                return (f64::INFINITY, f64::INFINITY);
+            }
+            let border = if direction > 0.0 { index + 1 } else { index } as f64 * spacing;
+            ((border - origin) / direction, spacing / direction.abs())
+        };
+        let origin = ray.origin() - self.corner;
+        let (mut next_x, delta_x) = crossing(i, dx, origin.x(), d.x());
+        let (mut next_z, delta_z) = crossing(j, dz, origin.z(), d.z());
+
+        loop {
+            if let Some(record) = self.hit_cell(ray, i, j, t_min, t_max) {
+                return Some(record);
+            }
+
+            // walk to the neighboring cell the ray crosses into first
+            if next_x < next_z {
+                if next_x > t_exit {
+                    return None;
+                }
+                i = step(i, d.x(), cells_x)?;
+                next_x += delta_x;
+            } else {
+                if next_z > t_exit {
+                    return None;
+                }
+                j = step(j, d.z(), cells_z)?;
+                next_z += delta_z;
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        Some(AABB::containing(&[self.corner, self.corner + self.size]))
+    }
+}
+
+impl From<Heightfield> for HittableObject {
+    fn from(heightfield: Heightfield) -> Self {
+        Self::Object(Box::new(heightfield))
+    }
+}
+
+/// The neighboring cell index in the direction, `None` if it's outside of the grid.
+fn step(index: usize, direction: f64, cells: usize) -> Option<usize> {
+    if direction > 0.0 {
+        Some(index + 1).filter(|&index| index < cells)
+    } else {
+        index.checked_sub(1)
+    }
+}
+
+/// Hit a triangle by the Möller–Trumbore algorithm, return the ray parameter and the barycentric
+/// coordinates of the second and third vertex.
+fn hit_triangle(
+    ray: &Ray,
+    [a, b, c]: [Vec3; 3],
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, f64, f64)> {
+    let (e1, e2) = (b - a, c - a);
+    let p = ray.direction().cross(e2);
+    let determinant = e1.dot(p);
+    if determinant.abs() < 1e-12 {
+        // the ray is parallel to the triangle
+        return None;
+    }
+
+    let s = ray.origin() - a;
+    let b1 = s.dot(p) / determinant;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let q = s.cross(e1);
+    let b2 = ray.direction().dot(q) / determinant;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = e2.dot(q) / determinant;
+    if t < t_min || t > t_max {
+        return None;
+    }
+
+    Some((t, b1, b2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Pointing;
+
+    /// A ramp rising along x from height 0 to 1 over the unit square.
+    fn ramp() -> Heightfield {
+        let (width, depth) = (5, 4);
+        let heights = (0..width * depth)
+            .map(|index| (index % width) as f64 / (width - 1) as f64)
+            .collect();
+
+        Heightfield::from_heights(
+            width,
+            depth,
+            heights,
+            Vec3::origin(),
+            Vec3::new(1.0, 1.0, 1.0),
+        )
+    }
+
+    #[test]
+    fn heightfield_hit() {
+        let ramp = ramp();
+        let expected_normal = Vec3::new(-1.0, 1.0, 0.0).normalized();
+
+        let down = Ray::new(Vec3::new(0.3, 2.0, 0.6), Vec3::new(0.0, -1.0, 0.0));
+        let record = ramp.hit(&down, 0.0, f64::INFINITY).unwrap();
+        assert!((record.hit_at - Vec3::new(0.3, 0.3, 0.6)).norm() < 1e-9);
+        assert!((record.normal - expected_normal).norm() < 1e-9);
+        assert!((record.u - 0.3).abs() < 1e-9 && (record.v - 0.4).abs() < 1e-9);
+        assert_eq!(record.pointing, Pointing::Outward);
+
+        // a slanted ray walking through several cells before hitting the ramp
+        let slanted = Ray::new(Vec3::new(-1.0, 0.9, 0.1), Vec3::new(1.0, -0.1, 0.05));
+        let record = ramp.hit(&slanted, 0.0, f64::INFINITY).unwrap();
+        assert!((record.hit_at.y() - record.hit_at.x()).abs() < 1e-9);
+        assert!((record.hit_at.x() - 8.0 / 11.0).abs() < 1e-9);
+
+        // rays passing above the ramp or outside of the grid
+        let above = Ray::new(Vec3::new(-1.0, 1.5, 0.5), Vec3::new(1.0, 0.0, 0.0));
+        assert!(ramp.hit(&above, 0.0, f64::INFINITY).is_none());
+        let outside = Ray::new(Vec3::new(1.5, 2.0, 0.5), Vec3::new(0.0, -1.0, 0.0));
+        assert!(ramp.hit(&outside, 0.0, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn heightfield_open() {
+        let path = std::env::temp_dir().join("heightfield_open.png");
+        let pixels = vec![0, u16::MAX, u16::MAX / 2, 0, 0, 0];
+        image::ImageBuffer::<image::Luma<u16>, _>::from_raw(3, 2, pixels)
+            .unwrap()
+            .save(&path)
+            .unwrap();
+
+        let size = Vec3::new(2.0, 4.0, 1.0);
+        let heightfield = Heightfield::open(&path, Vec3::origin(), size).unwrap();
+        assert_eq!((heightfield.width, heightfield.depth), (3, 2));
+        assert!((heightfield.vertex(1, 0) - Vec3::new(1.0, 4.0, 0.0)).norm() < 1e-9);
+        assert!((heightfield.vertex(2, 0).y() - 2.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn heightfield_interpolated_normals() {
+        // a single bump in the middle of a flat grid
+        let (width, depth) = (3, 3);
+        let mut heights = vec![0.0; width * depth];
+        heights[4] = 1.0;
+        let bump = Heightfield::from_heights(
+            width,
+            depth,
+            heights,
+            Vec3::origin(),
+            Vec3::new(2.0, 0.5, 2.0),
+        );
+
+        // the shading normal varies smoothly within a triangle and is upright at the top
+        let ray = |x: f64, z: f64| Ray::new(Vec3::new(x, 2.0, z), Vec3::new(0.0, -1.0, 0.0));
+        let top = bump.hit(&ray(1.0, 1.0), 0.0, f64::INFINITY).unwrap();
+        assert!((top.normal - Vec3::new(0.0, 1.0, 0.0)).norm() < 1e-9);
+
+        let near = bump.hit(&ray(0.9, 1.2), 0.0, f64::INFINITY).unwrap();
+        let far = bump.hit(&ray(0.5, 1.2), 0.0, f64::INFINITY).unwrap();
+        assert!(near.normal.x() < 0.0 && far.normal.x() < near.normal.x());
+        assert!((near.geometric_normal - far.geometric_normal).norm() < 1e-9);
+    }
+}
@@ -1,21 +1,34 @@
-use std::mem;
+use std::{f64::consts::PI, mem};
 
 use rand::Rng;
 
-use crate::{ray::Ray, Vec3};
+use crate::{ray::Ray, sampling::Onb, Vec3};
 
 /// Describes when, where and how a ray hit an object.
+#[derive(Clone)]
 pub struct HitRecord {
     /// Where did the ray hit the object.
     pub hit_at: Vec3,
-    /// The normal of the object at the hit point that's always
+    /// The shading normal of the object at the hit point that's always
     /// - on the same side as the ray origin with respect to the object surface
     /// - normalized to unit norm
+    ///
+    /// Materials may perturb the shading normal, e.g. by normal maps.
     pub normal: Vec3,
+    /// The true normal of the surface at the hit point, on the same side as the shading normal
+    /// and normalized to unit norm.
+    pub geometric_normal: Vec3,
+    /// The unit direction the first surface coordinate increases in, perpendicular to the shading
+    /// normal.
+    pub tangent: Vec3,
     /// The ray parameter when the hit occurred.
     pub t: f64,
     /// Where the normal points to.
     pub pointing: Pointing,
+    /// The first surface coordinate of the hit point in [0, 1], used to look up textures.
+    pub u: f64,
+    /// The second surface coordinate of the hit point in [0, 1], used to look up textures.
+    pub v: f64,
 }
 
 /// Where the normal points to.
@@ -28,7 +41,16 @@ pub enum Pointing {
 }
 
 impl HitRecord {
-    fn new(ray: &Ray, t: f64, outward_normal: Vec3) -> Self {
+    /// Construct a hit record given the ray parameter of the hit, the unit normal pointing to the
+    /// outside of the object, the surface coordinates and the derivative of the hit point with
+    /// respect to the first surface coordinate.
+    pub(crate) fn new(
+        ray: &Ray,
+        t: f64,
+        outward_normal: Vec3,
+        (u, v): (f64, f64),
+        dpdu: Vec3,
+    ) -> Self {
         let pointing = if ray.direction().same_direction(outward_normal) {
             Pointing::Inward
         } else {
@@ -43,10 +65,60 @@ impl HitRecord {
         Self {
             hit_at: ray.at(t),
             normal,
+            geometric_normal: normal,
+            tangent: tangent(normal, dpdu),
             t,
             pointing,
+            u,
+            v,
+        }
+    }
+
+    /// Replace the shading normal, the tangent is kept perpendicular to the new normal.
+    pub fn with_shading_normal(&self, normal: Vec3) -> Self {
+        let normal = normal.normalized();
+
+        Self {
+            normal,
+            tangent: tangent(normal, self.tangent),
+            ..self.clone()
         }
     }
+
+    /// The unit direction the second surface coordinate increases in, perpendicular to both the
+    /// shading normal and the tangent.
+    pub fn bitangent(&self) -> Vec3 {
+        let bitangent = self.normal.cross(self.tangent);
+        match self.pointing {
+            Pointing::Outward => bitangent,
+            Pointing::Inward => -bitangent,
+        }
+    }
+
+    /// A point slightly off the surface on the side the given direction points to, rays leaving
+    /// the surface from there won't hit the surface again. The geometric normal decides the side,
+    /// so rays never leak through the surface due to perturbed shading normals.
+    pub fn spawn_point(&self, direction: Vec3) -> Vec3 {
+        const OFFSET: f64 = 1e-6;
+
+        let offset = OFFSET * self.hit_at.norm().max(1.0) * self.geometric_normal;
+        if direction.dot(self.geometric_normal) >= 0.0 {
+            self.hit_at + offset
+        } else {
+            self.hit_at - offset
+        }
+    }
+}
+
+/// The unit vector of the given direction projected onto the plane perpendicular to the normal,
+/// an arbitrary unit vector perpendicular to the normal if the direction is degenerate.
+fn tangent(normal: Vec3, direction: Vec3) -> Vec3 {
+    let projected = direction - direction.dot(normal) * normal;
+    if projected.norm_squared() > 1e-12 {
+        projected.normalized()
+    } else {
+        Onb::from_w(normal).u()
+    }
 }
 
 /// An object that may be hit by and reflect a ray.
@@ -61,16 +133,52 @@ pub trait Hittable {
     /// given range of ray parameter [t_min, t_max].
     fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
 
+    /// Return all hits of the ray with the object within the given range of ray parameter in
+    /// ascending order of the ray parameter, e.g. to find where rays enter and leave closed
+    /// objects. By default the object is hit repeatedly, each time just behind the previous hit.
+    fn intersections(&self, ray: &Ray, t_min: f64, t_max: f64) -> Vec<HitRecord> {
+        let mut records = vec![];
+        let mut t_min = t_min;
+
+        while let Some(record) = self.hit(ray, t_min, t_max) {
+            t_min = record.t + 1e-9 * record.t.abs().max(1.0);
+            records.push(record);
+        }
+
+        records
+    }
+
     /// Return an Axis-Aligned Bounding Box (AABB) containing the hittable object if the object is
     /// bounded. If a ray hits the object, it must also hit the bounding box.
     fn bounding_box(&self) -> Option<AABB>;
 }
 
+/// A point on the surface of an object.
+pub struct SurfaceSample {
+    /// The point on the surface.
+    pub point: Vec3,
+    /// The unit normal pointing to the outside of the object at the point.
+    pub normal: Vec3,
+}
+
+/// An object whose surface can be sampled uniformly by area, e.g. to use the object as an area
+/// light.
+pub trait Sampleable {
+    /// The area of the surface.
+    fn area(&self) -> f64;
+
+    /// A random point uniformly distributed on the surface, the PDF with respect to area is the
+    /// reciprocal of the area.
+    fn sample_surface<R: Rng>(&self, rng: &mut R) -> SurfaceSample;
+}
+
 /// An enum wrapping the boxed Hittable and a few named objects so methods of [Hittable](Hittable)
 /// still can be statically dispatched most of the time.
 pub enum HittableObject {
     /// An sphere.
     Sphere(Sphere),
+    /// A parallelogram.
+    Quad(Quad),
     /// A general [Hittable](Hittable) trait object.
     Object(Box<dyn Hittable + Send + Sync>),
 }
@@ -79,13 +187,23 @@ impl Hittable for HittableObject {
     fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
         match self {
             HittableObject::Sphere(sphere) => sphere.hit(ray, t_min, t_max),
+            HittableObject::Quad(quad) => quad.hit(ray, t_min, t_max),
             HittableObject::Object(obj) => obj.hit(ray, t_min, t_max),
         }
     }
 
+    fn intersections(&self, ray: &Ray, t_min: f64, t_max: f64) -> Vec<HitRecord> {
+        match self {
+            HittableObject::Sphere(sphere) => sphere.intersections(ray, t_min, t_max),
+            HittableObject::Quad(quad) => quad.intersections(ray, t_min, t_max),
+            HittableObject::Object(obj) => obj.intersections(ray, t_min, t_max),
+        }
+    }
+
     fn bounding_box(&self) -> Option<AABB> {
         match self {
             HittableObject::Sphere(sphere) => sphere.bounding_box(),
+            HittableObject::Quad(quad) => quad.bounding_box(),
             HittableObject::Object(obj) => obj.bounding_box(),
         }
     }
@@ -97,6 +215,12 @@ impl From<Sphere> for HittableObject {
     }
 }
 
+impl From<Quad> for HittableObject {
+    fn from(quad: Quad) -> Self {
+        Self::Quad(quad)
+    }
+}
+
 /// A sphere described by its center and radius.
 pub struct Sphere {
     /// Center of the sphere.
@@ -141,13 +265,15 @@ impl Hittable for Sphere {
         }
 
         let sqrt_d = discriminant.sqrt();
-        let root = std::array::IntoIter::new([(-half_b - sqrt_d) / a, (-half_b + sqrt_d) / a])
+        let root = IntoIterator::into_iter([(-half_b - sqrt_d) / a, (-half_b + sqrt_d) / a])
             .find(|&root| t_min <= root && root <= t_max)?;
 
         // must be normalized here: radius may be negative as a trick to describe the hollow inside
         // of a sphere
         let normal = (ray.at(root) - self.center) / self.radius;
-        Some(HitRecord::new(&ray, root, normal))
+        // the derivative of the point with respect to the angle around the y axis
+        let dpdu = Vec3::new(normal.z(), 0.0, -normal.x());
+        Some(HitRecord::new(ray, root, normal, sphere_uv(normal), dpdu))
     }
 
     fn bounding_box(&self) -> Option<AABB> {
@@ -159,6 +285,102 @@ impl Hittable for Sphere {
     }
 }
 
+impl Sampleable for Sphere {
+    fn area(&self) -> f64 {
+        4.0 * PI * self.radius.powi(2)
+    }
+
+    fn sample_surface<R: Rng>(&self, rng: &mut R) -> SurfaceSample {
+        let normal = random_unit(rng).normalized();
+        SurfaceSample {
+            point: self.center + self.radius.abs() * normal,
+            normal: normal * self.radius.signum(),
+        }
+    }
+}
+
+/// A parallelogram spanned by two edges from a corner, e.g. a wall or a leaf. The outward normal
+/// is the cross product of the edges, the surface coordinates run along the edges.
+pub struct Quad {
+    /// A corner of the parallelogram.
+    pub corner: Vec3,
+    /// The first edge from the corner.
+    pub u: Vec3,
+    /// The second edge from the corner.
+    pub v: Vec3,
+}
+
+impl Quad {
+    /// Construct a parallelogram spanned by the edges `u` and `v` from the corner.
+    pub fn new(corner: Vec3, u: Vec3, v: Vec3) -> Self {
+        Self { corner, u, v }
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let n = self.u.cross(self.v);
+        let denominator = n.dot(ray.direction());
+        if denominator.abs() < 1e-12 {
+            // the ray is parallel to the plane
+            return None;
+        }
+
+        let t = n.dot(self.corner - ray.origin()) / denominator;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        // coordinates of the hit point along the edges
+        let planar = ray.at(t) - self.corner;
+        let w = n / n.norm_squared();
+        let alpha = w.dot(planar.cross(self.v));
+        let beta = w.dot(self.u.cross(planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        Some(HitRecord::new(
+            ray,
+            t,
+            n.normalized(),
+            (alpha, beta),
+            self.u,
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        Some(AABB::containing(&[
+            self.corner,
+            self.corner + self.u,
+            self.corner + self.v,
+            self.corner + self.u + self.v,
+        ]))
+    }
+}
+
+impl Sampleable for Quad {
+    fn area(&self) -> f64 {
+        self.u.cross(self.v).norm()
+    }
+
+    fn sample_surface<R: Rng>(&self, rng: &mut R) -> SurfaceSample {
+        SurfaceSample {
+            point: self.corner + rng.gen::<f64>() * self.u + rng.gen::<f64>() * self.v,
+            normal: self.u.cross(self.v).normalized(),
+        }
+    }
+}
+
+/// Surface coordinates of a point on the unit sphere, u is the angle around the y axis starting
+/// from -x, v is the angle from -y to +y.
+fn sphere_uv(p: Vec3) -> (f64, f64) {
+    let theta = (-p.y()).clamp(-1.0, 1.0).acos();
+    let phi = (-p.z()).atan2(p.x()) + PI;
+
+    (phi / (2.0 * PI), theta / PI)
+}
+
 fn random_unit<R: Rng>(rng: &mut R) -> Vec3 {
     loop {
         let p = Vec3::new(
@@ -194,7 +416,13 @@ impl AABB {
     }
 
     /// Test if a ray hits an AABB.
-    pub fn hit(&self, ray: &Ray, mut t_min: f64, mut t_max: f64) -> bool {
+    pub fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> bool {
+        self.interval(ray, t_min, t_max).is_some()
+    }
+
+    /// The range of ray parameter where the ray is inside of the AABB, narrowed down from the given
+    /// range. Return `None` if the ray misses the AABB within the given range.
+    pub fn interval(&self, ray: &Ray, mut t_min: f64, mut t_max: f64) -> Option<(f64, f64)> {
         for i in 0..Vec3::DIMENSIONS {
             let (t0, t1) = {
                 // When ray.direction[i] == 0.0, inv_d == infinity (positive or negative), if
@@ -217,11 +445,11 @@ impl AABB {
 
             if t_max <= t_min {
                 // the possible range of t is now empty
-                return false;
+                return None;
             }
         }
 
-        true
+        Some((t_min, t_max))
     }
 
     /// Merge two AABBs, return a bigger AABB containing the two given AABBs.
@@ -236,6 +464,24 @@ impl AABB {
 
         Self::new(min, max)
     }
+
+    /// Return an AABB containing all the given points, padded by a small margin so flat objects
+    /// still have boxes of non-zero volume.
+    pub fn containing(points: &[Vec3]) -> Self {
+        const PADDING: f64 = 1e-4;
+
+        let mut min = Vec3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+        let mut max = -min;
+
+        for point in points {
+            for i in 0..Vec3::DIMENSIONS {
+                min[i] = min[i].min(point[i] - PADDING);
+                max[i] = max[i].max(point[i] + PADDING);
+            }
+        }
+
+        Self::new(min, max)
+    }
 }
 
 #[cfg(test)]
@@ -272,4 +518,54 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn sphere_tangent_frame() {
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..100 {
+            let origin = 3.0 * Sphere::unit().random_point_on_surface(&mut rng);
+            let ray = Ray::new(
+                origin,
+                Sphere::unit().random_point_in_sphere(&mut rng) - origin,
+            );
+            let record = Sphere::unit().hit(&ray, 0.0, f64::INFINITY).unwrap();
+
+            assert!((record.tangent.norm() - 1.0).abs() < 1e-9);
+            assert!(record.tangent.dot(record.normal).abs() < 1e-9);
+            assert!(record.bitangent().dot(record.tangent).abs() < 1e-9);
+
+            // moving along the tangent frame increases the surface coordinates
+            let step = |direction: Vec3| {
+                let p = record.hit_at + 1e-4 * direction;
+                sphere_uv(p.normalized())
+            };
+            let (u, _) = step(record.tangent);
+            let (_, v) = step(record.bitangent());
+            if 0.01 < record.u && record.u < 0.99 && 0.01 < record.v && record.v < 0.99 {
+                assert!(u > record.u && v > record.v);
+            }
+        }
+    }
+
+    #[test]
+    fn quad_hit() {
+        let quad = Quad::new(
+            Vec3::new(-1.0, -1.0, 0.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+        );
+
+        let ray = Ray::new(Vec3::new(0.5, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let record = quad.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!((record.t - 1.0).abs() < 1e-9);
+        assert!((record.u - 0.75).abs() < 1e-9 && (record.v - 0.5).abs() < 1e-9);
+        assert_eq!(record.pointing, Pointing::Outward);
+        assert!(quad.bounding_box().unwrap().hit(&ray, 0.0, f64::INFINITY));
+
+        let miss = Ray::new(Vec3::new(1.5, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(quad.hit(&miss, 0.0, f64::INFINITY).is_none());
+        let parallel = Ray::new(Vec3::new(0.0, 0.0, 1.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(quad.hit(&parallel, 0.0, f64::INFINITY).is_none());
+    }
 }
@@ -0,0 +1,446 @@
+use rand::Rng;
+
+use crate::{
+    color::{self, Rgb},
+    hittable::Sphere,
+    medium::Medium,
+    ray::Ray,
+    spectrum,
+    world::{HitEvent, World},
+};
+
+/// A unidirectional path tracer.
+///
+/// Paths are extended one bounce at a time until they escape the world, get absorbed or reach the
+/// maximum depth. At every non-specular bounce, light sources without geometry are sampled by
+/// shadow rays, the background is sampled as well if it supports importance sampling, combined
+/// with the light carried by the next bounce by multiple importance sampling. After a minimum
+/// depth, paths are randomly terminated by Russian roulette with a
+/// probability based on the throughput carried so far, the surviving paths are weighted up
+/// accordingly so the estimate stays unbiased.
+///
+/// Rays scattered into a participating medium take a random walk through it, every scattering
+/// event inside the medium counts as a bounce.
+///
+/// Once a path is restricted to a single wavelength, either by a dispersive material or by the
+/// spectral mode, all colors it meets afterwards are uplifted to spectra and evaluated at that
+/// wavelength.
+pub struct PathTracer {
+    max_depth: usize,
+    roulette_depth: usize,
+    spectral: bool,
+}
+
+impl Default for PathTracer {
+    fn default() -> Self {
+        Self {
+            max_depth: 64,
+            roulette_depth: 3,
+            spectral: false,
+        }
+    }
+}
+
+impl PathTracer {
+    /// Initialize a path tracer with the default parameters.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum number of bounces of a path.
+    ///
+    /// # Default:
+    /// 64
+    pub fn max_depth(&mut self, max_depth: usize) -> &mut Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Set the number of bounces a path must survive before Russian roulette is applied. Russian
+    /// roulette is effectively disabled when it's not smaller than the maximum depth.
+    ///
+    /// # Default:
+    /// 3
+    pub fn roulette_depth(&mut self, roulette_depth: usize) -> &mut Self {
+        self.roulette_depth = roulette_depth;
+        self
+    }
+
+    /// Enable or disable the spectral mode. In spectral mode every camera ray carries a single
+    /// randomly sampled wavelength, the radiance of the path is converted back to the output color
+    /// space through the CIE XYZ color matching functions. Spectral mode converges slower than RGB
+    /// mode, but handles wavelength dependent effects consistently.
+    ///
+    /// # Default:
+    /// false
+    pub fn spectral(&mut self, spectral: bool) -> &mut Self {
+        self.spectral = spectral;
+        self
+    }
+
+    /// Estimate the color of the light arriving at the origin of the ray.
+    pub fn ray_color<R: Rng>(&self, rng: &mut R, ray: &Ray, world: &World) -> Rgb {
+        if self.spectral && ray.wavelength().is_none() {
+            let wavelength = spectrum::sample_wavelength(rng);
+            let ray = ray.clone().with_wavelength(wavelength);
+            // the radiance of a single wavelength is gray
+            return self.trace(rng, &ray, world) * spectrum::wavelength_to_rgb(wavelength);
+        }
+
+        self.trace(rng, ray, world)
+    }
+
+    fn trace<R: Rng>(&self, rng: &mut R, ray: &Ray, world: &World) -> Rgb {
+        let mut radiance = color::BLACK;
+        let mut throughput = color::WHITE;
+        let mut ray = ray.clone();
+        // the PDF of sampling the direction of the current ray at the last bounce, `None` for
+        // camera rays and rays scattered by delta distributions
+        let mut scatter_pdf = None;
+        // the medium the current ray travels through
+        let mut medium: Option<Medium> = None;
+
+        for depth in 0..self.max_depth {
+            let hit = world.hit(rng, &ray, 0.001, f64::INFINITY);
+
+            if let Some(medium) = medium {
+                let length = ray.direction().norm();
+                let max_distance = hit.as_ref().map_or(f64::INFINITY, |e| e.record.t * length);
+                let sample = medium.at(ray.wavelength()).sample(rng, max_distance);
+                throughput = throughput * sample.weight;
+
+                if sample.scattered {
+                    // isotropic scattering
+                    let direction = Sphere::unit().random_point_on_surface(rng);
+                    let scattered = Ray::new(ray.at(sample.distance / length), direction);
+                    ray = match ray.wavelength() {
+                        Some(wavelength) => scattered.with_wavelength(wavelength),
+                        None => scattered,
+                    };
+                    scatter_pdf = None;
+
+                    throughput = match self.roulette(rng, depth, throughput) {
+                        Some(throughput) => throughput,
+                        None => return radiance,
+                    };
+                    continue;
+                }
+            }
+
+            let event = match hit {
+                Some(event) => event,
+                None => {
+                    let background = world.background();
+                    let weight = match scatter_pdf {
+                        Some(pdf) => power_heuristic(pdf, background.pdf(ray.direction())),
+                        None => 1.0,
+                    };
+
+                    let background_radiance =
+                        project(background.radiance(ray.direction()), ray.wavelength());
+                    return radiance + weight * (throughput * background_radiance);
+                }
+            };
+
+            // emitters are only found by chance, they're not sampled as light sources
+            let emitted = event.material.emitted(&ray, &event.record);
+            radiance += throughput * project(emitted, ray.wavelength());
+
+            let scatter = match &event.scatter {
+                Some(scatter) => scatter,
+                None => return radiance,
+            };
+
+            if !scatter.is_specular() {
+                radiance += throughput * sample_lights(rng, &ray, &event, world);
+                radiance += throughput * sample_background(rng, &ray, &event, world);
+            }
+
+            scatter_pdf = scatter.pdf();
+            medium = scatter.medium;
+            throughput = throughput * project(scatter.attenuation, ray.wavelength());
+            let wavelength = scatter.wavelength.or_else(|| ray.wavelength());
+            ray = Ray::new(
+                event.record.spawn_point(scatter.direction),
+                scatter.direction,
+            );
+            if let Some(wavelength) = wavelength {
+                ray = ray.with_wavelength(wavelength);
+            }
+
+            throughput = match self.roulette(rng, depth, throughput) {
+                Some(throughput) => throughput,
+                None => return radiance,
+            };
+        }
+
+        radiance
+    }
+
+    /// Apply Russian roulette after the bounce at the given depth, return the weighted up
+    /// throughput if the path survives.
+    fn roulette<R: Rng>(&self, rng: &mut R, depth: usize, throughput: Rgb) -> Option<Rgb> {
+        // the highest probability a path survives the roulette, so paths bouncing between perfect
+        // mirrors are still terminated eventually
+        const MAXIMUM_SURVIVAL: f64 = 0.95;
+
+        if depth + 1 < self.roulette_depth {
+            return Some(throughput);
+        }
+
+        let survival = throughput.max_channel().min(MAXIMUM_SURVIVAL);
+        if rng.gen::<f64>() >= survival {
+            None
+        } else {
+            Some(throughput / survival)
+        }
+    }
+}
+
+/// Direct lighting from all light sources without geometry at a hit point, each light source is
+/// tested for visibility with a shadow ray.
+fn sample_lights<R: Rng>(rng: &mut R, ray: &Ray, event: &HitEvent, world: &World) -> Rgb {
+    const SHADOW_EPSILON: f64 = 0.001;
+
+    let HitEvent {
+        record, material, ..
+    } = event;
+    let mut radiance = color::BLACK;
+
+    for light in world.lights() {
+        let sample = match light.sample(rng, record.hit_at) {
+            Some(sample) => sample,
+            None => continue,
+        };
+
+        let f = project(
+            material.eval(ray, record, sample.direction),
+            ray.wavelength(),
+        );
+        if f.max_channel() <= 0.0 {
+            continue;
+        }
+
+        let shadow_ray = Ray::new(record.spawn_point(sample.direction), sample.direction);
+        if !world.occluded(
+            rng,
+            &shadow_ray,
+            SHADOW_EPSILON,
+            sample.distance - SHADOW_EPSILON,
+        ) {
+            radiance += f * project(sample.radiance, ray.wavelength());
+        }
+    }
+
+    radiance
+}
+
+/// Direct lighting from the background at a hit point by importance sampling the background,
+/// weighted by the power heuristic against sampling the material.
+fn sample_background<R: Rng>(rng: &mut R, ray: &Ray, event: &HitEvent, world: &World) -> Rgb {
+    const SHADOW_EPSILON: f64 = 0.001;
+
+    let HitEvent {
+        record, material, ..
+    } = event;
+
+    let (sample, light_pdf) = match world.background().sample(rng) {
+        Some(sample) => sample,
+        None => return color::BLACK,
+    };
+
+    let f = project(
+        material.eval(ray, record, sample.direction),
+        ray.wavelength(),
+    );
+    if f.max_channel() <= 0.0 {
+        return color::BLACK;
+    }
+
+    let shadow_ray = Ray::new(record.spawn_point(sample.direction), sample.direction);
+    if world.occluded(rng, &shadow_ray, SHADOW_EPSILON, f64::INFINITY) {
+        return color::BLACK;
+    }
+
+    let weight = power_heuristic(light_pdf, material.pdf(ray, record, sample.direction));
+    (weight / light_pdf) * (f * project(sample.radiance, ray.wavelength()))
+}
+
+/// Uplift a color to a spectrum and evaluate it at the given wavelength as a gray color, colors are
+/// left as is if the light isn't restricted to a single wavelength.
+fn project(rgb: Rgb, wavelength: Option<f64>) -> Rgb {
+    match wavelength {
+        Some(wavelength) => {
+            let value = spectrum::rgb_to_spectrum(rgb, wavelength);
+            Rgb::new(value, value, value)
+        }
+        None => rgb,
+    }
+}
+
+/// The weight of a sample drawn from the strategy with PDF `f` where another strategy with PDF `g`
+/// may also have generated the sample, with exponent 2.
+fn power_heuristic(f: f64, g: f64) -> f64 {
+    let (f2, g2) = (f * f, g * g);
+    if f2 + g2 == 0.0 {
+        0.0
+    } else {
+        f2 / (f2 + g2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::{
+        camera::CameraBuilder,
+        material::{tests::Glow, Conductor, Lambertian, Material, Metal},
+        world::WorldBuilder,
+        Vec3,
+    };
+
+    fn mean_and_variance(samples: &[f64]) -> (f64, f64) {
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+        let variance = samples.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        (mean, variance)
+    }
+
+    #[test]
+    fn roulette_unbiased() {
+        const WIDTH: u32 = 8;
+        const HEIGHT: u32 = 8;
+        const SAMPLE_PER_PIXEL: usize = 200;
+
+        let mut builder = WorldBuilder::new();
+        builder.add(
+            Sphere {
+                center: Vec3::new(0.0, -100.5, -1.0),
+                radius: 100.0,
+            },
+            Lambertian::new(Rgb::new(0.8, 0.8, 0.8)),
+        );
+        builder.add(
+            Sphere {
+                center: Vec3::new(0.0, 0.0, -1.0),
+                radius: 0.5,
+            },
+            Lambertian::new(Rgb::new(0.7, 0.3, 0.3)),
+        );
+        builder.add(
+            Sphere {
+                center: Vec3::new(1.0, 0.0, -1.0),
+                radius: 0.5,
+            },
+            Metal::new(Rgb::new(0.8, 0.6, 0.2), 0.3),
+        );
+        let world = builder.build().unwrap();
+        let camera = CameraBuilder::new().aspect_ratio(1.0).build();
+
+        let render = |tracer: &PathTracer, seed: u64| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut samples = vec![];
+
+            for sampler in camera.cast(WIDTH, HEIGHT) {
+                for _ in 0..SAMPLE_PER_PIXEL {
+                    let ray = sampler.sample(&mut rng);
+                    let rgb = tracer.ray_color(&mut rng, &ray, &world);
+                    samples.push((rgb.r() + rgb.g() + rgb.b()) / 3.0);
+                }
+            }
+
+            mean_and_variance(&samples)
+        };
+
+        let mut reference = PathTracer::new();
+        reference.roulette_depth(usize::MAX);
+        let (reference_mean, reference_variance) = render(&reference, 0);
+
+        let mut roulette = PathTracer::new();
+        roulette.roulette_depth(1);
+        let (roulette_mean, roulette_variance) = render(&roulette, 1);
+
+        let n = (WIDTH * HEIGHT) as f64 * SAMPLE_PER_PIXEL as f64;
+        let std_error = ((reference_variance + roulette_variance) / n).sqrt();
+        assert!(
+            (reference_mean - roulette_mean).abs() < 4.0 * std_error,
+            "reference mean {}, roulette mean {}, standard error {}",
+            reference_mean,
+            roulette_mean,
+            std_error
+        );
+    }
+
+    #[test]
+    fn spectral_matches_rgb() {
+        const WIDTH: u32 = 8;
+        const HEIGHT: u32 = 8;
+        const SAMPLE_PER_PIXEL: usize = 200;
+
+        let mut builder = WorldBuilder::new();
+        builder.add(
+            Sphere {
+                center: Vec3::new(0.0, -100.5, -1.0),
+                radius: 100.0,
+            },
+            Lambertian::new(Rgb::new(0.5, 0.5, 0.5)),
+        );
+        builder.add(
+            Sphere {
+                center: Vec3::new(0.0, 0.0, -1.0),
+                radius: 0.5,
+            },
+            Conductor::aluminium(0.3),
+        );
+        let world = builder.build().unwrap();
+        let camera = CameraBuilder::new().aspect_ratio(1.0).build();
+
+        let render = |tracer: &PathTracer, seed: u64| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut sum = color::BLACK;
+
+            for sampler in camera.cast(WIDTH, HEIGHT) {
+                for _ in 0..SAMPLE_PER_PIXEL {
+                    let ray = sampler.sample(&mut rng);
+                    sum += tracer.ray_color(&mut rng, &ray, &world);
+                }
+            }
+
+            sum / (WIDTH * HEIGHT) as f64 / SAMPLE_PER_PIXEL as f64
+        };
+
+        let rgb = render(&PathTracer::new(), 0);
+        let spectral = render(PathTracer::new().spectral(true), 1);
+
+        // the sky is only approximately reproduced by uplifting, gray materials don't shift it
+        for (a, b) in [
+            (rgb.r(), spectral.r()),
+            (rgb.g(), spectral.g()),
+            (rgb.b(), spectral.b()),
+        ]
+        .iter()
+        {
+            assert!((a - b).abs() < 0.1, "rgb {}, spectral {}", a, b);
+        }
+    }
+
+    #[test]
+    fn custom_emission() {
+        let mut rng = rand::thread_rng();
+        let mut builder = WorldBuilder::new();
+        builder.add(
+            Sphere {
+                center: Vec3::origin(),
+                radius: 10.0,
+            },
+            Material::custom(Glow(Rgb::new(0.5, 1.0, 2.0))),
+        );
+        let world = builder.build().unwrap();
+
+        let ray = Ray::new(Vec3::origin(), Vec3::new(1.0, 2.0, 3.0));
+        let rgb = PathTracer::new().ray_color(&mut rng, &ray, &world);
+        assert!((*rgb - *Rgb::new(0.5, 1.0, 2.0)).norm() < 1e-9);
+    }
+}
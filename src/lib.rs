@@ -1,7 +1,7 @@
 //! A CPU-based ray tracer.
 
 #![deny(missing_docs)]
-#![allow(clippy::clippy::upper_case_acronyms)]
+#![allow(clippy::upper_case_acronyms)]
 
 /// A trait generalizing image file types.
 pub mod image_builder;
@@ -9,21 +9,66 @@ pub mod image_builder;
 /// Color types and color constants.
 pub mod color;
 
+/// Wavelength dependent quantities and their conversion to colors.
+pub mod spectrum;
+
 /// Ray in 3-dimensional space.
 pub mod ray;
 
 /// Objects that may be hit and reflect a ray.
 pub mod hittable;
 
+/// Cylinders, cones, disks and annuli.
+pub mod quadric;
+
+/// Tori and the polynomial root finder intersecting rays with them.
+pub mod torus;
+
+/// Constructive solid geometry combining closed objects.
+pub mod csg;
+
+/// Procedural shapes described by signed distance functions.
+pub mod sdf;
+
+/// Terrain described by heightmaps.
+pub mod heightfield;
+
+/// Bicubic Bézier patches and the loader of patch files.
+pub mod bezier;
+
 /// A camera from where all rays originate.
 pub mod camera;
 
+/// Colors and scalar parameters varying over the surface of objects.
+pub mod texture;
+
 /// Materials with different optical properties.
 pub mod material;
 
+/// Orthonormal bases and helpers for sampling directions.
+pub mod sampling;
+
+/// Microfacet distributions and Fresnel equations.
+pub mod microfacet;
+
+/// Participating media scattering light inside objects.
+pub mod medium;
+
+/// Light sources without geometry.
+pub mod light;
+
+/// Backgrounds and environment lights seen by rays escaping the world.
+pub mod environment;
+
+/// Analytic daylight sky model.
+pub mod sky;
+
 /// A collection of hittable objects and their materials.
 pub mod world;
 
+/// Integrators estimating the light carried by a ray.
+pub mod integrator;
+
 use derive_more::{Index, IndexMut};
 
 use std::{
@@ -50,6 +95,10 @@ pub enum Error {
     /// Comparing two NaNs for order.
     #[error("Comparing two NaNs for order")]
     ComparingNan,
+
+    /// A file of Bézier patches doesn't follow the expected format.
+    #[error("Malformed patch file")]
+    MalformedPatchFile,
 }
 
 impl Debug for Error {
@@ -222,7 +271,7 @@ impl NonNan {
 
 impl Eq for NonNan {}
 
-#[allow(clippy::clippy::derive_ord_xor_partial_ord)]
+#[allow(clippy::derive_ord_xor_partial_ord)]
 impl Ord for NonNan {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         self.partial_cmp(other).unwrap()
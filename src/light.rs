@@ -0,0 +1,240 @@
+use rand::Rng;
+
+use crate::{
+    color::Rgb,
+    sampling::{self, Onb},
+    Vec3,
+};
+
+/// The light arriving at a point from a light source.
+pub struct LightSample {
+    /// The unit direction from the point towards the light source.
+    pub direction: Vec3,
+    /// The distance between the point and the light source, infinite for light sources infinitely
+    /// far away.
+    pub distance: f64,
+    /// The radiance arriving at the point, already integrated over the light source.
+    pub radiance: Rgb,
+}
+
+/// Light sources without geometry. None of them can be hit by a ray, they only contribute to the
+/// image via shadow rays.
+pub enum Light {
+    /// A point light emitting uniformly in all directions.
+    Point(PointLight),
+    /// A point light emitting in a cone.
+    Spot(SpotLight),
+    /// A light infinitely far away emitting in a single direction, e.g. the sun.
+    Directional(DirectionalLight),
+}
+
+impl Light {
+    /// Sample the light arriving at the given point, return `None` if no light from the light
+    /// source can possibly arrive at the point.
+    pub fn sample<R: Rng>(&self, rng: &mut R, point: Vec3) -> Option<LightSample> {
+        match self {
+            Light::Point(l) => l.sample(point),
+            Light::Spot(l) => l.sample(point),
+            Light::Directional(l) => l.sample(rng),
+        }
+    }
+}
+
+impl From<PointLight> for Light {
+    fn from(l: PointLight) -> Self {
+        Self::Point(l)
+    }
+}
+
+impl From<SpotLight> for Light {
+    fn from(l: SpotLight) -> Self {
+        Self::Spot(l)
+    }
+}
+
+impl From<DirectionalLight> for Light {
+    fn from(l: DirectionalLight) -> Self {
+        Self::Directional(l)
+    }
+}
+
+/// A point light emitting uniformly in all directions.
+pub struct PointLight {
+    position: Vec3,
+    intensity: Rgb,
+}
+
+impl PointLight {
+    /// Construct a point light at the given position with the given radiant intensity.
+    pub fn new(position: Vec3, intensity: Rgb) -> Self {
+        Self {
+            position,
+            intensity,
+        }
+    }
+
+    fn sample(&self, point: Vec3) -> Option<LightSample> {
+        let to_light = self.position - point;
+        let distance = to_light.norm();
+        if distance == 0.0 {
+            return None;
+        }
+
+        Some(LightSample {
+            direction: to_light / distance,
+            distance,
+            radiance: self.intensity / distance.powi(2),
+        })
+    }
+}
+
+/// A point light emitting in a cone. The intensity is constant within the inner cone and smoothly
+/// falls off to zero at the border of the outer cone.
+pub struct SpotLight {
+    position: Vec3,
+    direction: Vec3,
+    intensity: Rgb,
+    cos_total_width: f64,
+    cos_falloff_start: f64,
+}
+
+impl SpotLight {
+    /// Construct a spot light at the given position pointing to the `look_at` point with the
+    /// given radiant intensity. `total_width` is the half angle of the outer cone in degrees,
+    /// `falloff_start` is the half angle of the inner cone in degrees.
+    pub fn new(
+        position: Vec3,
+        look_at: Vec3,
+        intensity: Rgb,
+        total_width: f64,
+        falloff_start: f64,
+    ) -> Self {
+        assert!(falloff_start <= total_width);
+
+        Self {
+            position,
+            direction: (look_at - position).normalized(),
+            intensity,
+            cos_total_width: total_width.to_radians().cos(),
+            cos_falloff_start: falloff_start.to_radians().cos(),
+        }
+    }
+
+    fn falloff(&self, cos_theta: f64) -> f64 {
+        smooth_step(self.cos_total_width, self.cos_falloff_start, cos_theta)
+    }
+
+    fn sample(&self, point: Vec3) -> Option<LightSample> {
+        let to_light = self.position - point;
+        let distance = to_light.norm();
+        if distance == 0.0 {
+            return None;
+        }
+
+        let direction = to_light / distance;
+        let falloff = self.falloff((-direction).dot(self.direction));
+        if falloff <= 0.0 {
+            return None;
+        }
+
+        Some(LightSample {
+            direction,
+            distance,
+            radiance: (falloff / distance.powi(2)) * self.intensity,
+        })
+    }
+}
+
+/// A light infinitely far away emitting in a single direction, e.g. the sun. The light may come
+/// from a small disk instead of a single direction to cast soft shadows.
+pub struct DirectionalLight {
+    direction: Vec3,
+    irradiance: Rgb,
+    cos_angular_radius: f64,
+}
+
+impl DirectionalLight {
+    /// Construct a directional light with light travelling in the given direction, arriving with
+    /// the given irradiance on a surface perpendicular to the direction.
+    pub fn new(direction: Vec3, irradiance: Rgb) -> Self {
+        Self::disk(direction, irradiance, 0.0)
+    }
+
+    /// Construct a directional light coming from a disk of the given angular radius in degrees,
+    /// e.g. the sun seen from the earth has an angular radius of about 0.27 degrees.
+    pub fn disk(direction: Vec3, irradiance: Rgb, angular_radius: f64) -> Self {
+        Self {
+            direction: direction.normalized(),
+            irradiance,
+            cos_angular_radius: angular_radius.to_radians().cos(),
+        }
+    }
+
+    fn sample<R: Rng>(&self, rng: &mut R) -> Option<LightSample> {
+        let direction = if self.cos_angular_radius < 1.0 {
+            let local = sampling::random_cone_direction(rng, self.cos_angular_radius);
+            Onb::from_w(-self.direction).local_to_world(local)
+        } else {
+            -self.direction
+        };
+
+        Some(LightSample {
+            direction,
+            distance: f64::INFINITY,
+            radiance: self.irradiance,
+        })
+    }
+}
+
+fn smooth_step(lo: f64, hi: f64, x: f64) -> f64 {
+    if lo == hi {
+        return if x < lo { 0.0 } else { 1.0 };
+    }
+
+    let t = ((x - lo) / (hi - lo)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color;
+
+    #[test]
+    fn point_light_inverse_square() {
+        let light = PointLight::new(Vec3::new(0.0, 2.0, 0.0), color::WHITE);
+
+        let near = light.sample(Vec3::new(0.0, 1.0, 0.0)).unwrap();
+        let far = light.sample(Vec3::new(0.0, -2.0, 0.0)).unwrap();
+
+        assert!((near.distance - 1.0).abs() < 1e-9);
+        assert!((far.distance - 4.0).abs() < 1e-9);
+        assert!((near.radiance.r() / far.radiance.r() - 16.0).abs() < 1e-9);
+        assert!((far.direction - Vec3::new(0.0, 1.0, 0.0)).norm() < 1e-9);
+    }
+
+    #[test]
+    fn spot_light_cone() {
+        let light = SpotLight::new(
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::origin(),
+            color::WHITE,
+            30.0,
+            20.0,
+        );
+
+        // inside the inner cone
+        let inner = light.sample(Vec3::new(0.1, 0.0, 0.0)).unwrap();
+        assert!((inner.radiance.r() * inner.distance.powi(2) - 1.0).abs() < 1e-9);
+
+        // between the inner and the outer cone
+        let edge = light.sample(Vec3::new(25f64.to_radians().tan(), 0.0, 0.0));
+        let falloff = edge.unwrap().radiance.r() * (1.0 / 25f64.to_radians().cos()).powi(2);
+        assert!(0.0 < falloff && falloff < 1.0);
+
+        // outside the outer cone
+        assert!(light.sample(Vec3::new(1.0, 0.0, 0.0)).is_none());
+        // behind the light
+        assert!(light.sample(Vec3::new(0.0, 2.0, 0.0)).is_none());
+    }
+}
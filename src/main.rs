@@ -5,12 +5,12 @@ use rand::{prelude::SliceRandom, rngs::StdRng, Rng, SeedableRng};
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use raytracing::{
     camera::CameraBuilder,
-    color::{Rgb, RgbAccumulator, BLACK, LIGHTBLUE, WHITE},
+    color::{Rgb, RgbAccumulator},
     hittable::Sphere,
     image_builder::{ImageBuilder, PNGBuilder},
+    integrator::PathTracer,
     material::{Dielectric, Lambertian, Material, Metal},
-    ray::Ray,
-    world::{HitEvent, World, WorldBuilder},
+    world::{World, WorldBuilder},
     Error, Vec3,
 };
 
@@ -38,6 +38,7 @@ fn exec() -> anyhow::Result<()> {
         .build();
 
     let world = random_world(&mut rand::rngs::StdRng::from_entropy())?;
+    let tracer = PathTracer::new();
 
     let mut image_builder = PNGBuilder::with_dimensions(image_width, image_height);
 
@@ -56,7 +57,7 @@ fn exec() -> anyhow::Result<()> {
 
             for _ in 0..SAMPLE_PER_PIXEL {
                 let ray = sampler.sample(&mut rng);
-                let pixel = ray_color(&mut rng, &ray, &world);
+                let pixel = tracer.ray_color(&mut rng, &ray, &world);
                 acc.feed(pixel);
             }
 
@@ -156,43 +157,3 @@ fn error_exit<T: Display>(err: T) {
     eprintln!("{:#}", err);
     process::exit(1);
 }
-
-fn ray_color<R: Rng>(rng: &mut R, ray: &Ray, world: &World) -> Rgb {
-    const MAXIMUM_REFLECTION: usize = 64;
-    let mut attenuations = Vec::with_capacity(MAXIMUM_REFLECTION);
-
-    let mut reflect_cnt = 0;
-    let mut ray = ray.clone();
-
-    loop {
-        if reflect_cnt >= MAXIMUM_REFLECTION {
-            attenuations.push(BLACK);
-            break;
-        }
-
-        if let Some(event) = world.hit(rng, &ray, 0.001, f64::INFINITY) {
-            let HitEvent { record, scatter } = event;
-            let attenuation = if let Some(scatter) = scatter {
-                ray = Ray::new(record.hit_at, scatter.direction);
-                scatter.attenuation
-            } else {
-                attenuations.push(BLACK);
-                break;
-            };
-
-            attenuations.push(attenuation);
-            reflect_cnt += 1;
-        } else {
-            attenuations.push(background(&ray));
-            break;
-        }
-    }
-
-    attenuations.into_iter().fold(WHITE, |p, rgb| p * rgb)
-}
-
-fn background(ray: &Ray) -> Rgb {
-    let unit_dir = ray.direction().normalized();
-    let t = 0.5 * (unit_dir.y() + 1.0);
-    (1.0 - t) * WHITE + t * LIGHTBLUE
-}
@@ -1,9 +1,14 @@
-use rand::{distributions::Standard, prelude::Distribution, Rng};
+use rand::{distributions::Standard, prelude::Distribution, Rng, RngCore};
 
 use crate::{
     color::{self, Rgb},
     hittable::{HitRecord, Pointing, Sphere},
+    medium::Medium,
+    microfacet::{self, Ggx, ThinFilm},
     ray::Ray,
+    sampling::{self, Onb},
+    spectrum::{self, Ior},
+    texture::Texture,
     Vec3,
 };
 
@@ -11,29 +16,225 @@ use crate::{
 pub struct Scatter {
     /// The direction of the scattered light.
     pub direction: Vec3,
-    /// the attenuation (what does that even mean) of the scattered light.
+    /// The attenuation of the scattered light, i.e. the BSDF times the cosine term divided by the
+    /// PDF of sampling the scattered direction.
     pub attenuation: Rgb,
+    /// The kind of lobe the direction was sampled from.
+    pub lobe: Lobe,
+    /// The single wavelength in nanometers the scattered light is restricted to by dispersion,
+    /// `None` if the scattering doesn't restrict the wavelength.
+    pub wavelength: Option<f64>,
+    /// The medium the scattered light travels through, `None` if it travels through vacuum.
+    pub medium: Option<Medium>,
+}
+
+impl Scatter {
+    /// A scattering event sampled from a delta distribution.
+    pub fn specular(direction: Vec3, attenuation: Rgb) -> Self {
+        Self {
+            direction,
+            attenuation,
+            lobe: Lobe::Specular,
+            wavelength: None,
+            medium: None,
+        }
+    }
+
+    /// A scattering event sampled from a non-specular lobe with the given PDF.
+    pub fn sampled(direction: Vec3, attenuation: Rgb, pdf: f64) -> Self {
+        Self {
+            direction,
+            attenuation,
+            lobe: Lobe::NonSpecular { pdf },
+            wavelength: None,
+            medium: None,
+        }
+    }
+
+    /// Restrict the scattered light to a single wavelength in nanometers.
+    pub fn with_wavelength(self, wavelength: f64) -> Self {
+        Self {
+            wavelength: Some(wavelength),
+            ..self
+        }
+    }
+
+    /// Let the scattered light travel through the given medium.
+    pub fn with_medium(self, medium: Medium) -> Self {
+        Self {
+            medium: Some(medium),
+            ..self
+        }
+    }
+
+    /// Return whether the direction was sampled from a delta distribution.
+    pub fn is_specular(&self) -> bool {
+        matches!(self.lobe, Lobe::Specular)
+    }
+
+    /// The PDF with respect to solid angle of sampling the scattered direction, `None` if the
+    /// direction was sampled from a delta distribution.
+    pub fn pdf(&self) -> Option<f64> {
+        match self.lobe {
+            Lobe::Specular => None,
+            Lobe::NonSpecular { pdf } => Some(pdf),
+        }
+    }
+}
+
+/// The kind of lobe a scattered direction was sampled from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Lobe {
+    /// A delta distribution, e.g. a perfect mirror or a smooth glass interface. The BSDF cannot be
+    /// evaluated for any other direction, thus the PDF is meaningless.
+    Specular,
+    /// A non-specular lobe, the direction was sampled with the given PDF with respect to solid
+    /// angle.
+    NonSpecular {
+        /// The PDF of sampling the scattered direction.
+        pdf: f64,
+    },
 }
 
 /// Materials with different optical properties.
 pub enum Material {
     /// Lambertian materials, always scatter light randomly in Lambertian distribution.
     Lambertian(Lambertian),
+    /// Rough diffuse materials, scatter light by the Oren-Nayar model.
+    OrenNayar(OrenNayar),
     /// Metals, reflect light roughly to the opposite direction.
     Metal(Metal),
     /// Dielectric material, always refract light.
     Dielectric(Dielectric),
+    /// Physically based metals with microfacet roughness.
+    Conductor(Conductor),
+    /// Dielectric material with microfacet roughness, e.g. frosted glass.
+    RoughDielectric(RoughDielectric),
+    /// A principled material blending diffuse, specular, glass and clearcoat lobes.
+    Principled(Box<Principled>),
+    /// A dielectric coat over another material.
+    Coated(Coated),
+    /// Subsurface scattering materials, e.g. skin, wax or marble.
+    Subsurface(Subsurface),
+    /// A material with the shading normal perturbed by a normal map or a bump map.
+    NormalMapped(NormalMapped),
+    /// A material with different front and back materials.
+    TwoSided(TwoSided),
+    /// A general [Scatterer](Scatterer) trait object.
+    Custom(Box<dyn Scatterer + Send + Sync>),
 }
 
 impl Material {
+    /// Wrap a custom shading model into a material.
+    pub fn custom<S: Scatterer + Send + Sync + 'static>(scatterer: S) -> Self {
+        Self::Custom(Box::new(scatterer))
+    }
+
     /// Scatter lights after a hit event on the material.
     pub fn scatter<R: Rng>(&self, rng: &mut R, ray: &Ray, record: &HitRecord) -> Option<Scatter> {
         match self {
-            Material::Lambertian(l) => Some(l.scatter(rng, record.normal)),
+            Material::Lambertian(l) => Some(l.scatter(rng, record)),
+            Material::OrenNayar(o) => Some(o.scatter(rng, ray, record)),
             Material::Metal(m) => m.scatter(rng, ray, record.normal),
             Material::Dielectric(d) => Some(d.scatter(rng, ray, record)),
+            Material::Conductor(c) => c.scatter(rng, ray, record),
+            Material::RoughDielectric(d) => d.scatter(rng, ray, record),
+            Material::Principled(p) => p.scatter(rng, ray, record),
+            Material::Coated(c) => c.scatter(rng, ray, record),
+            Material::Subsurface(s) => Some(s.scatter(rng, ray, record)),
+            Material::NormalMapped(n) => n.scatter(rng, ray, record),
+            Material::TwoSided(t) => t.scatter(rng, ray, record),
+            Material::Custom(c) => c.scatter(rng, ray, record),
+        }
+    }
+
+    /// Evaluate the BSDF times the cosine term for light scattered to the given direction. Always
+    /// black for delta distributions.
+    pub fn eval(&self, ray: &Ray, record: &HitRecord, direction: Vec3) -> Rgb {
+        match self {
+            Material::Lambertian(l) => l.eval(record, direction),
+            Material::OrenNayar(o) => o.eval(ray, record, direction),
+            Material::Conductor(c) => c.eval(ray, record, direction),
+            Material::RoughDielectric(d) => d.eval(ray, record, direction),
+            Material::Principled(p) => p.eval(ray, record, direction),
+            Material::Coated(c) => c.eval(ray, record, direction),
+            Material::Subsurface(s) => s.eval(ray, record, direction),
+            Material::NormalMapped(n) => n.eval(ray, record, direction),
+            Material::TwoSided(t) => t.eval(ray, record, direction),
+            Material::Custom(c) => c.eval(ray, record, direction),
+            Material::Metal(_) | Material::Dielectric(_) => color::BLACK,
         }
     }
+
+    /// The PDF with respect to solid angle of [scatter](Material::scatter) sampling the given
+    /// direction. Always zero for delta distributions.
+    pub fn pdf(&self, ray: &Ray, record: &HitRecord, direction: Vec3) -> f64 {
+        match self {
+            Material::Lambertian(l) => l.pdf(record, direction),
+            Material::OrenNayar(o) => o.pdf(record, direction),
+            Material::Conductor(c) => c.pdf(ray, record, direction),
+            Material::RoughDielectric(d) => d.pdf(ray, record, direction),
+            Material::Principled(p) => p.pdf(ray, record, direction),
+            Material::Coated(c) => c.pdf(ray, record, direction),
+            Material::Subsurface(s) => s.pdf(ray, record, direction),
+            Material::NormalMapped(n) => n.pdf(ray, record, direction),
+            Material::TwoSided(t) => t.pdf(ray, record, direction),
+            Material::Custom(c) => c.pdf(ray, record, direction),
+            Material::Metal(_) | Material::Dielectric(_) => 0.0,
+        }
+    }
+
+    /// The radiance emitted by the material towards the origin of the ray.
+    pub fn emitted(&self, ray: &Ray, record: &HitRecord) -> Rgb {
+        match self {
+            Material::Coated(c) => c.emitted(ray, record),
+            Material::NormalMapped(n) => n.base.emitted(ray, &n.shading_record(record)),
+            Material::TwoSided(t) => {
+                let (side, side_record) = t.side(record);
+                side.emitted(ray, &side_record)
+            }
+            Material::Custom(c) => c.emitted(ray, record),
+            _ => color::BLACK,
+        }
+    }
+}
+
+/// A shading model defined outside of this crate, wrapped into a material by
+/// [Material::custom](Material::custom).
+///
+/// Only [scatter](Scatterer::scatter) is required, which is enough for materials sampled from
+/// delta distributions. Materials with non-specular lobes must also implement
+/// [eval](Scatterer::eval) and [pdf](Scatterer::pdf) consistently with the scattered directions,
+/// i.e. the attenuation of a sampled direction equals the evaluated BSDF over the PDF, so light
+/// sources can be sampled at the hit point.
+pub trait Scatterer {
+    /// Scatter lights after a hit event on the material.
+    fn scatter(&self, rng: &mut dyn RngCore, ray: &Ray, record: &HitRecord) -> Option<Scatter>;
+
+    /// Evaluate the BSDF times the cosine term for light scattered to the given direction.
+    ///
+    /// # Default:
+    /// black, as for delta distributions
+    fn eval(&self, _ray: &Ray, _record: &HitRecord, _direction: Vec3) -> Rgb {
+        color::BLACK
+    }
+
+    /// The PDF with respect to solid angle of [scatter](Scatterer::scatter) sampling the given
+    /// direction.
+    ///
+    /// # Default:
+    /// zero, as for delta distributions
+    fn pdf(&self, _ray: &Ray, _record: &HitRecord, _direction: Vec3) -> f64 {
+        0.0
+    }
+
+    /// The radiance emitted by the material towards the origin of the ray.
+    ///
+    /// # Default:
+    /// black, the material doesn't emit light
+    fn emitted(&self, _ray: &Ray, _record: &HitRecord) -> Rgb {
+        color::BLACK
+    }
 }
 
 impl From<Lambertian> for Material {
@@ -42,6 +243,12 @@ impl From<Lambertian> for Material {
     }
 }
 
+impl From<OrenNayar> for Material {
+    fn from(o: OrenNayar) -> Self {
+        Self::OrenNayar(o)
+    }
+}
+
 impl From<Metal> for Material {
     fn from(m: Metal) -> Self {
         Self::Metal(m)
@@ -54,6 +261,48 @@ impl From<Dielectric> for Material {
     }
 }
 
+impl From<Conductor> for Material {
+    fn from(c: Conductor) -> Self {
+        Self::Conductor(c)
+    }
+}
+
+impl From<RoughDielectric> for Material {
+    fn from(d: RoughDielectric) -> Self {
+        Self::RoughDielectric(d)
+    }
+}
+
+impl From<Principled> for Material {
+    fn from(p: Principled) -> Self {
+        Self::Principled(Box::new(p))
+    }
+}
+
+impl From<Coated> for Material {
+    fn from(c: Coated) -> Self {
+        Self::Coated(c)
+    }
+}
+
+impl From<Subsurface> for Material {
+    fn from(s: Subsurface) -> Self {
+        Self::Subsurface(s)
+    }
+}
+
+impl From<NormalMapped> for Material {
+    fn from(n: NormalMapped) -> Self {
+        Self::NormalMapped(n)
+    }
+}
+
+impl From<TwoSided> for Material {
+    fn from(t: TwoSided) -> Self {
+        Self::TwoSided(t)
+    }
+}
+
 /// Lambertian materials, always scatter light randomly in Lambertian distribution.
 pub struct Lambertian {
     albedo: Rgb,
@@ -65,23 +314,106 @@ impl Lambertian {
         Self { albedo }
     }
 
-    fn scatter<R: Rng>(&self, rng: &mut R, normal: Vec3) -> Scatter {
-        let mut direction = normal + Sphere::unit().random_point_on_surface(rng);
-        if direction.near_zero() {
-            direction = normal;
+    fn scatter<R: Rng>(&self, rng: &mut R, record: &HitRecord) -> Scatter {
+        let onb = Onb::from_w(record.normal);
+        let local = sampling::random_cosine_direction(rng);
+        let direction = onb.local_to_world(local);
+
+        // the albedo over pi times the cosine term cancels out with the cosine-weighted PDF
+        Scatter::sampled(direction, self.albedo, sampling::cosine_pdf(local.z()))
+    }
+
+    fn eval(&self, record: &HitRecord, direction: Vec3) -> Rgb {
+        let cos_theta = record.normal.dot(direction.normalized());
+        sampling::cosine_pdf(cos_theta) * self.albedo
+    }
+
+    fn pdf(&self, record: &HitRecord, direction: Vec3) -> f64 {
+        sampling::cosine_pdf(record.normal.dot(direction.normalized()))
+    }
+}
+
+/// Rough diffuse materials, e.g. clay, concrete or fabric, scatter light by the Oren-Nayar model
+/// ("Generalization of Lambert's Reflectance Model", Oren and Nayar 1994) of V-shaped Lambertian
+/// microfacets. Rough surfaces look flatter than Lambertian ones and reflect more light back to
+/// the light source.
+pub struct OrenNayar {
+    albedo: Rgb,
+    a: f64,
+    b: f64,
+}
+
+impl OrenNayar {
+    /// Construct an Oren-Nayar material with the given color and the standard deviation of the
+    /// microfacet slope angle in degrees. A zero sigma is Lambertian.
+    pub fn new(albedo: Rgb, sigma: f64) -> Self {
+        let sigma2 = sigma.to_radians().powi(2);
+
+        Self {
+            albedo,
+            a: 1.0 - sigma2 / (2.0 * (sigma2 + 0.33)),
+            b: 0.45 * sigma2 / (sigma2 + 0.09),
         }
+    }
 
-        Scatter {
+    fn scatter<R: Rng>(&self, rng: &mut R, ray: &Ray, record: &HitRecord) -> Scatter {
+        let onb = Onb::from_w(record.normal);
+        let local = sampling::random_cosine_direction(rng);
+        let direction = onb.local_to_world(local);
+
+        // the cosine-weighted PDF cancels out the Lambertian part of the BRDF
+        let wo = onb.world_to_local(-ray.direction().normalized());
+        Scatter::sampled(
             direction,
-            attenuation: self.albedo,
-        }
+            self.factor(wo, local) * self.albedo,
+            sampling::cosine_pdf(local.z()),
+        )
+    }
+
+    /// The BRDF over the Lambertian BRDF of the same albedo.
+    fn factor(&self, wo: Vec3, wi: Vec3) -> f64 {
+        let sin_o = (1.0 - wo.z() * wo.z()).max(0.0).sqrt();
+        let sin_i = (1.0 - wi.z() * wi.z()).max(0.0).sqrt();
+
+        let cos_phi = if sin_o > 1e-4 && sin_i > 1e-4 {
+            ((wo.x() * wi.x() + wo.y() * wi.y()) / (sin_o * sin_i)).max(0.0)
+        } else {
+            0.0
+        };
+
+        // sin(alpha) * tan(beta), where alpha is the larger and beta the smaller polar angle
+        let (cos_o, cos_i) = (wo.z().abs(), wi.z().abs());
+        let (sin_alpha, tan_beta) = if cos_i > cos_o {
+            (sin_o, sin_i / cos_i)
+        } else {
+            (sin_i, sin_o / cos_o.max(1e-6))
+        };
+
+        self.a + self.b * cos_phi * sin_alpha * tan_beta
+    }
+
+    fn eval(&self, ray: &Ray, record: &HitRecord, direction: Vec3) -> Rgb {
+        let onb = Onb::from_w(record.normal);
+        let wo = onb.world_to_local(-ray.direction().normalized());
+        let wi = onb.world_to_local(direction.normalized());
+
+        (sampling::cosine_pdf(wi.z()) * self.factor(wo, wi)) * self.albedo
+    }
+
+    fn pdf(&self, record: &HitRecord, direction: Vec3) -> f64 {
+        sampling::cosine_pdf(record.normal.dot(direction.normalized()))
     }
 }
 
 /// Metals, reflect light roughly to the opposite direction.
+///
+/// A metal covered by a thin film reflects light by the Fresnel equations instead, with the
+/// complex index of refraction fitted to the color (Gulbrandsen 2014, "Artist Friendly Metallic
+/// Fresnel").
 pub struct Metal {
     albedo: Rgb,
     fuzz: f64,
+    film: Option<ThinFilm>,
 }
 
 impl Metal {
@@ -90,26 +422,207 @@ impl Metal {
         Self {
             albedo,
             fuzz: fuzz.min(1.0),
+            film: None,
+        }
+    }
+
+    /// Cover the metal by a thin film of the given thickness in nanometers and index of
+    /// refraction, e.g. the oxide layer of anodized metals.
+    pub fn with_thin_film(self, thickness: f64, ior: f64) -> Self {
+        Self {
+            film: Some(ThinFilm::new(thickness, ior)),
+            ..self
         }
     }
 
+    fn reflectance(&self, ray: &Ray, normal: Vec3) -> Rgb {
+        let film = match &self.film {
+            Some(film) => film,
+            None => return self.albedo,
+        };
+
+        let [(eta_r, k_r), (eta_g, k_g), (eta_b, k_b)] =
+            [self.albedo.r(), self.albedo.g(), self.albedo.b()].map(artist_friendly_ior);
+        let cos_i = -ray.direction().normalized().dot(normal);
+
+        film_reflectance(
+            film,
+            cos_i,
+            1.0,
+            Rgb::new(eta_r, eta_g, eta_b),
+            Rgb::new(k_r, k_g, k_b),
+            ray.wavelength(),
+        )
+    }
+
     /// Construct a metal material with the given color.
     pub fn scatter<R: Rng>(&self, rng: &mut R, ray: &Ray, normal: Vec3) -> Option<Scatter> {
         let reflected = reflect(ray.direction().normalized(), normal);
         let direction = reflected + self.fuzz * Sphere::unit().random_point_in_sphere(rng);
 
         // the surface absorbs all rays fuzzed into it.
+        // fuzzed reflection has no closed form BSDF, it's treated as a delta distribution
         if reflected.same_direction(normal) {
-            Some(Scatter {
-                direction,
-                attenuation: self.albedo,
-            })
+            Some(Scatter::specular(direction, self.reflectance(ray, normal)))
         } else {
             None
         }
     }
 }
 
+/// Physically based metals, reflect light by microfacets in the GGX distribution with the Fresnel
+/// reflectance of the complex index of refraction.
+///
+/// The index of refraction is given per color channel, sampled at 650nm, 550nm and 450nm for red,
+/// green and blue respectively. Light of a single wavelength sees the index of refraction uplifted
+/// to a spectrum.
+pub struct Conductor {
+    eta: Rgb,
+    k: Rgb,
+    distribution: Ggx,
+    film: Option<ThinFilm>,
+}
+
+impl Conductor {
+    /// Construct a conductor with the given complex index of refraction `eta + i k` and a
+    /// perceptually linear roughness in [0, 1].
+    pub fn new(eta: Rgb, k: Rgb, roughness: f64) -> Self {
+        Self {
+            eta,
+            k,
+            distribution: Ggx::new(roughness),
+            film: None,
+        }
+    }
+
+    /// Cover the conductor by a thin film of the given thickness in nanometers and index of
+    /// refraction, e.g. the oxide layer of anodized metals.
+    pub fn with_thin_film(self, thickness: f64, ior: f64) -> Self {
+        Self {
+            film: Some(ThinFilm::new(thickness, ior)),
+            ..self
+        }
+    }
+
+    /// Gold with the given roughness.
+    pub fn gold(roughness: f64) -> Self {
+        Self::new(
+            Rgb::new(0.143, 0.374, 1.442),
+            Rgb::new(3.983, 2.385, 1.603),
+            roughness,
+        )
+    }
+
+    /// Copper with the given roughness.
+    pub fn copper(roughness: f64) -> Self {
+        Self::new(
+            Rgb::new(0.200, 0.924, 1.102),
+            Rgb::new(3.912, 2.452, 2.142),
+            roughness,
+        )
+    }
+
+    /// Aluminium with the given roughness.
+    pub fn aluminium(roughness: f64) -> Self {
+        Self::new(
+            Rgb::new(1.657, 0.880, 0.521),
+            Rgb::new(9.224, 6.270, 4.837),
+            roughness,
+        )
+    }
+
+    fn fresnel(&self, cos_i: f64, wavelength: Option<f64>) -> Rgb {
+        if let Some(film) = &self.film {
+            return film_reflectance(film, cos_i, 1.0, self.eta, self.k, wavelength);
+        }
+
+        if let Some(wavelength) = wavelength {
+            let eta = spectrum::rgb_to_spectrum(self.eta, wavelength);
+            let k = spectrum::rgb_to_spectrum(self.k, wavelength);
+            let f = microfacet::fresnel_conductor(cos_i, eta, k);
+            return Rgb::new(f, f, f);
+        }
+
+        Rgb::new(
+            microfacet::fresnel_conductor(cos_i, self.eta.r(), self.k.r()),
+            microfacet::fresnel_conductor(cos_i, self.eta.g(), self.k.g()),
+            microfacet::fresnel_conductor(cos_i, self.eta.b(), self.k.b()),
+        )
+    }
+
+    fn scatter<R: Rng>(&self, rng: &mut R, ray: &Ray, record: &HitRecord) -> Option<Scatter> {
+        let onb = Onb::from_w(record.normal);
+        let wo = onb.world_to_local(-ray.direction().normalized());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        if self.distribution.is_smooth() {
+            let wi = microfacet::reflect(wo, Vec3::new(0.0, 0.0, 1.0));
+            return Some(Scatter::specular(
+                onb.local_to_world(wi),
+                self.fresnel(wo.z(), ray.wavelength()),
+            ));
+        }
+
+        let wh = self.distribution.sample_visible(wo, rng.gen(), rng.gen());
+        let wi = microfacet::reflect(wo, wh);
+        // the sampled direction is under the surface, the light is absorbed
+        if wi.z() <= 0.0 {
+            return None;
+        }
+
+        let cos_h = wo.dot(wh);
+        let pdf = self.distribution.pdf_visible(wo, wh) / (4.0 * cos_h);
+        // BSDF: F * D * G / (4 * cos_o * cos_i), most terms cancel out with the PDF
+        let weight = self.distribution.g(wo, wi) / self.distribution.g1(wo);
+
+        Some(Scatter::sampled(
+            onb.local_to_world(wi),
+            weight * self.fresnel(cos_h, ray.wavelength()),
+            pdf,
+        ))
+    }
+
+    fn local_directions(
+        &self,
+        ray: &Ray,
+        record: &HitRecord,
+        direction: Vec3,
+    ) -> Option<(Vec3, Vec3, Vec3)> {
+        if self.distribution.is_smooth() {
+            return None;
+        }
+
+        let onb = Onb::from_w(record.normal);
+        let wo = onb.world_to_local(-ray.direction().normalized());
+        let wi = onb.world_to_local(direction.normalized());
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return None;
+        }
+
+        let wh = (wo + wi).normalized();
+        Some((wo, wi, wh))
+    }
+
+    fn eval(&self, ray: &Ray, record: &HitRecord, direction: Vec3) -> Rgb {
+        match self.local_directions(ray, record, direction) {
+            Some((wo, wi, wh)) => {
+                let f = self.distribution.d(wh) * self.distribution.g(wo, wi) / (4.0 * wo.z());
+                f * self.fresnel(wo.dot(wh), ray.wavelength())
+            }
+            None => color::BLACK,
+        }
+    }
+
+    fn pdf(&self, ray: &Ray, record: &HitRecord, direction: Vec3) -> f64 {
+        match self.local_directions(ray, record, direction) {
+            Some((wo, _, wh)) => self.distribution.pdf_visible(wo, wh) / (4.0 * wo.dot(wh)),
+            None => 0.0,
+        }
+    }
+}
+
 fn refract(uv: Vec3, normal: Vec3, etai_over_etat: f64) -> Vec3 {
     let cos_theta = (-uv).dot(normal).min(1.0);
     let r_out_perp = etai_over_etat * (uv + cos_theta * normal);
@@ -119,21 +632,83 @@ fn refract(uv: Vec3, normal: Vec3, etai_over_etat: f64) -> Vec3 {
 }
 
 /// Dielectric material, always refract light.
+///
+/// Light travelling inside the material may be absorbed following the Beer-Lambert law, so thick
+/// glass looks darker and more saturated than thin glass of the same material. The objects made of
+/// absorbing dielectrics must be closed.
+///
+/// The index of refraction may depend on the wavelength, light hitting such a dispersive material
+/// is restricted to a single randomly sampled wavelength, so white light splits into rainbows.
+///
+/// The surface may be covered by a thin film, e.g. soap bubbles, the reflectance then depends on
+/// the wavelength and the reflected light is iridescent.
 #[derive(Clone, Copy)]
 pub struct Dielectric {
-    ir: f64,
+    ior: Ior,
+    absorption: Rgb,
+    film: Option<ThinFilm>,
 }
 
 impl Dielectric {
-    /// Construct a dielectric material with the given index of refraction.
+    /// Construct a clear dielectric material with the given index of refraction.
     pub fn new(ir: f64) -> Self {
-        Self { ir }
+        Self::absorbing(ir, color::BLACK)
+    }
+
+    /// Construct a clear dielectric material with the given wavelength dependent index of
+    /// refraction.
+    pub fn dispersive(ior: Ior) -> Self {
+        Self {
+            ior,
+            absorption: color::BLACK,
+            film: None,
+        }
+    }
+
+    /// Construct a dielectric material with the given index of refraction and absorption
+    /// coefficients per unit length.
+    pub fn absorbing(ir: f64, absorption: Rgb) -> Self {
+        Self {
+            ior: Ior::Constant(ir),
+            absorption,
+            film: None,
+        }
+    }
+
+    /// Cover the material by a thin film of the given thickness in nanometers and index of
+    /// refraction. A bubble is a dielectric with an index of refraction of 1 covered by a film.
+    pub fn with_thin_film(self, thickness: f64, ior: f64) -> Self {
+        Self {
+            film: Some(ThinFilm::new(thickness, ior)),
+            ..self
+        }
+    }
+
+    /// Construct a dielectric material with the given index of refraction, white light is tinted
+    /// to the given color after travelling the given distance inside the material.
+    pub fn tinted(ir: f64, color: Rgb, distance: f64) -> Self {
+        Self::absorbing(ir, absorption_coefficients(color, distance))
     }
 
     fn scatter<R: Rng>(&self, rng: &mut R, ray: &Ray, record: &HitRecord) -> Scatter {
+        // white light is split by sampling a single wavelength, weighted by its color
+        let (wavelength, weight) = match ray.wavelength() {
+            Some(wavelength) => (Some(wavelength), color::WHITE),
+            None if self.ior.is_dispersive() => {
+                let wavelength = spectrum::sample_wavelength(rng);
+                (Some(wavelength), spectrum::wavelength_to_rgb(wavelength))
+            }
+            None => (None, color::WHITE),
+        };
+
+        let ir = match wavelength {
+            Some(wavelength) => self.ior.at(wavelength),
+            None => self.ior.nominal(),
+        };
+
         let refraction_ratio = match record.pointing {
-            Pointing::Inward => self.ir,
-            Pointing::Outward => 1.0 / self.ir,
+            Pointing::Inward => ir,
+            Pointing::Outward => 1.0 / ir,
         };
 
         let unit_direction = ray.direction().normalized();
@@ -142,43 +717,1498 @@ impl Dielectric {
 
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
 
-        let direction = if cannot_refract || reflectance(cos_theta, refraction_ratio) > rng.gen() {
+        let (reflected, fresnel) = match &self.film {
+            _ if cannot_refract => (true, color::WHITE),
+            None => (
+                reflectance(cos_theta, refraction_ratio) > rng.gen(),
+                color::WHITE,
+            ),
+            Some(film) => {
+                let (outside, inside) = match record.pointing {
+                    Pointing::Inward => (ir, 1.0),
+                    Pointing::Outward => (1.0, ir),
+                };
+                let substrate = Rgb::new(inside, inside, inside);
+                let r = film_reflectance(
+                    film,
+                    cos_theta,
+                    outside,
+                    substrate,
+                    color::BLACK,
+                    wavelength,
+                );
+
+                // choose by the average reflectance, weight the colors accordingly
+                let p = ((r.r() + r.g() + r.b()) / 3.0).clamp(0.0, 1.0);
+                if rng.gen::<f64>() < p {
+                    (true, r / p)
+                } else {
+                    let t = Rgb::new(1.0 - r.r(), 1.0 - r.g(), 1.0 - r.b());
+                    (false, t / (1.0 - p))
+                }
+            }
+        };
+
+        let direction = if reflected {
             reflect(unit_direction, record.normal)
         } else {
             refract(unit_direction, record.normal, refraction_ratio)
         };
 
-        Scatter {
+        let scatter = Scatter::specular(
             direction,
-            attenuation: color::WHITE,
+            weight * fresnel * path_transmittance(self.absorption, ray, record),
+        );
+        match wavelength {
+            Some(wavelength) if self.ior.is_dispersive() => scatter.with_wavelength(wavelength),
+            _ => scatter,
         }
     }
 }
 
-fn reflect(direction: Vec3, normal: Vec3) -> Vec3 {
-    direction - 2.0 * direction.dot(normal) * normal
-}
+/// The absorption coefficients per unit length tinting white light to the given color after
+/// travelling the given distance.
+fn absorption_coefficients(color: Rgb, distance: f64) -> Rgb {
+    assert!(distance > 0.0);
 
-fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
-    let r0 = ((1.0 - ref_idx) / (1.0 + ref_idx)).powi(2);
-    r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+    let coefficient = |channel: f64| -channel.max(f64::MIN_POSITIVE).ln() / distance;
+    Rgb::new(
+        coefficient(color.r()),
+        coefficient(color.g()),
+        coefficient(color.b()),
+    )
 }
 
-impl Distribution<Lambertian> for Standard {
-    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Lambertian {
-        Lambertian::new(rng.gen::<Rgb>() * rng.gen::<Rgb>())
+/// The fraction of light left after travelling from the ray origin to the hit point through a
+/// material with the given absorption coefficients, only applies when the ray hit the material
+/// from the inside.
+fn path_transmittance(absorption: Rgb, ray: &Ray, record: &HitRecord) -> Rgb {
+    match record.pointing {
+        Pointing::Inward => {
+            let distance = record.t * ray.direction().norm();
+            let channel = |a: f64| (-a * distance).exp();
+            Rgb::new(
+                channel(absorption.r()),
+                channel(absorption.g()),
+                channel(absorption.b()),
+            )
+        }
+        Pointing::Outward => color::WHITE,
     }
 }
 
-impl Distribution<Metal> for Standard {
-    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Metal {
-        let albedo = Rgb::new(
-            rng.gen_range(0.5..1.0),
-            rng.gen_range(0.5..1.0),
-            rng.gen_range(0.5..1.0),
-        );
-        let fuzz = rng.gen_range(0.0..0.5);
+/// Dielectric material with microfacet roughness in the GGX distribution (Walter et al. 2007,
+/// "Microfacet Models for Refraction through Rough Surfaces"), both the reflection and the
+/// transmission lobe are weighted by the exact dielectric Fresnel reflectance.
+///
+/// Light travelling inside the material may be absorbed like in [Dielectric](Dielectric), the
+/// objects made of absorbing rough dielectrics must be closed.
+///
+/// # Deviation from PBRT
+/// Transmitted radiance is not scaled by the squared ratio of the indices of refraction, to stay
+/// consistent with [Dielectric](Dielectric). The scaling cancels out for paths entering and
+/// leaving the same object.
+pub struct RoughDielectric {
+    ir: f64,
+    distribution: Ggx,
+    absorption: Rgb,
+}
 
-        Metal::new(albedo, fuzz)
+impl RoughDielectric {
+    /// Construct a clear rough dielectric material with the given index of refraction and a
+    /// perceptually linear roughness in [0, 1].
+    pub fn new(ir: f64, roughness: f64) -> Self {
+        Self::absorbing(ir, roughness, color::BLACK)
+    }
+
+    /// Construct a rough dielectric material with the given index of refraction, roughness and
+    /// absorption coefficients per unit length.
+    pub fn absorbing(ir: f64, roughness: f64, absorption: Rgb) -> Self {
+        Self {
+            ir,
+            distribution: Ggx::new(roughness),
+            absorption,
+        }
+    }
+
+    /// Construct a rough dielectric material with the given index of refraction and roughness,
+    /// white light is tinted to the given color after travelling the given distance inside the
+    /// material.
+    pub fn tinted(ir: f64, roughness: f64, color: Rgb, distance: f64) -> Self {
+        Self::absorbing(ir, roughness, absorption_coefficients(color, distance))
+    }
+
+    /// The relative index of refraction (transmitted over incident) and the local shading frame.
+    fn local_frame(&self, ray: &Ray, record: &HitRecord) -> (f64, Onb, Vec3) {
+        let eta = match record.pointing {
+            Pointing::Inward => 1.0 / self.ir,
+            Pointing::Outward => self.ir,
+        };
+
+        let onb = Onb::from_w(record.normal);
+        let wo = onb.world_to_local(-ray.direction().normalized());
+        (eta, onb, wo)
+    }
+
+    fn scatter<R: Rng>(&self, rng: &mut R, ray: &Ray, record: &HitRecord) -> Option<Scatter> {
+        let (eta, onb, wo) = self.local_frame(ray, record);
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        if self.distribution.is_smooth() {
+            let normal = Vec3::new(0.0, 0.0, 1.0);
+            let reflectance = microfacet::fresnel_dielectric(wo.z(), eta);
+            let wi = match microfacet::refract(wo, normal, eta) {
+                Some(wi) if rng.gen::<f64>() >= reflectance => wi,
+                _ => microfacet::reflect(wo, normal),
+            };
+
+            return Some(Scatter::specular(
+                onb.local_to_world(wi),
+                path_transmittance(self.absorption, ray, record),
+            ));
+        }
+
+        let wm = self.distribution.sample_visible(wo, rng.gen(), rng.gen());
+        let cos_m = wo.dot(wm);
+        let reflectance = microfacet::fresnel_dielectric(cos_m, eta);
+        let pdf_wm = self.distribution.pdf_visible(wo, wm);
+
+        let (wi, pdf) = if rng.gen::<f64>() < reflectance {
+            let wi = microfacet::reflect(wo, wm);
+            if wi.z() <= 0.0 {
+                return None;
+            }
+
+            (wi, reflectance * pdf_wm / (4.0 * cos_m))
+        } else {
+            let wi = microfacet::refract(wo, wm, eta)?;
+            if wi.z() >= 0.0 {
+                return None;
+            }
+
+            let denominator = (wi.dot(wm) + cos_m / eta).powi(2);
+            (
+                wi,
+                (1.0 - reflectance) * pdf_wm * wi.dot(wm).abs() / denominator,
+            )
+        };
+
+        // BSDF times cosine over PDF, the same for both lobes as Fresnel terms cancel out
+        let weight = self.distribution.g(wo, wi) / self.distribution.g1(wo);
+        Some(Scatter::sampled(
+            onb.local_to_world(wi),
+            weight * path_transmittance(self.absorption, ray, record),
+            pdf,
+        ))
+    }
+
+    /// The microfacet normal mapping `wo` to `wi`, `None` if no microfacet facing both directions
+    /// can do so.
+    fn half_vector(&self, wo: Vec3, wi: Vec3, eta: f64) -> Option<Vec3> {
+        let reflected = wi.z() > 0.0;
+        let wm = if reflected { wo + wi } else { wi * eta + wo };
+        if wm.norm_squared() == 0.0 {
+            return None;
+        }
+
+        let wm = wm.normalized();
+        let wm = if wm.z() < 0.0 { -wm } else { wm };
+
+        // discard back facing microfacets
+        let facing_wo = wm.dot(wo) > 0.0;
+        let facing_wi = if reflected {
+            wm.dot(wi) > 0.0
+        } else {
+            wm.dot(wi) < 0.0
+        };
+
+        if facing_wo && facing_wi {
+            Some(wm)
+        } else {
+            None
+        }
+    }
+
+    fn eval(&self, ray: &Ray, record: &HitRecord, direction: Vec3) -> Rgb {
+        let (eta, onb, wo) = self.local_frame(ray, record);
+        let wi = onb.world_to_local(direction.normalized());
+        if self.distribution.is_smooth() || wo.z() <= 0.0 || wi.z() == 0.0 {
+            return color::BLACK;
+        }
+
+        let wm = match self.half_vector(wo, wi, eta) {
+            Some(wm) => wm,
+            None => return color::BLACK,
+        };
+
+        let cos_m = wo.dot(wm);
+        let reflectance = microfacet::fresnel_dielectric(cos_m, eta);
+        let dg = self.distribution.d(wm) * self.distribution.g(wo, wi);
+
+        let f = if wi.z() > 0.0 {
+            reflectance * dg / (4.0 * wo.z())
+        } else {
+            let denominator = (wi.dot(wm) + cos_m / eta).powi(2);
+            (1.0 - reflectance) * dg * (wi.dot(wm) * cos_m).abs() / (wo.z() * denominator)
+        };
+
+        f * path_transmittance(self.absorption, ray, record)
+    }
+
+    fn pdf(&self, ray: &Ray, record: &HitRecord, direction: Vec3) -> f64 {
+        let (eta, onb, wo) = self.local_frame(ray, record);
+        let wi = onb.world_to_local(direction.normalized());
+        if self.distribution.is_smooth() || wo.z() <= 0.0 || wi.z() == 0.0 {
+            return 0.0;
+        }
+
+        let wm = match self.half_vector(wo, wi, eta) {
+            Some(wm) => wm,
+            None => return 0.0,
+        };
+
+        let cos_m = wo.dot(wm);
+        let reflectance = microfacet::fresnel_dielectric(cos_m, eta);
+        let pdf_wm = self.distribution.pdf_visible(wo, wm);
+
+        if wi.z() > 0.0 {
+            reflectance * pdf_wm / (4.0 * cos_m)
+        } else {
+            let denominator = (wi.dot(wm) + cos_m / eta).powi(2);
+            (1.0 - reflectance) * pdf_wm * wi.dot(wm).abs() / denominator
+        }
+    }
+}
+
+/// A dielectric coat over another material, e.g. varnished wood or car paint.
+///
+/// Light is either reflected by the coat interface according to the dielectric Fresnel
+/// reflectance, or refracted into the coat, scattered by the base material and refracted out
+/// again. Light passing through the coat is attenuated by the Fresnel transmittance at both
+/// crossings and by the absorption of the coat along the slanted path.
+///
+/// # Deviation from Weidlich and Wilkie
+/// Directions are not bent by the coat when the base material is evaluated, and multiple
+/// reflections between the coat and the base are ignored.
+pub struct Coated {
+    base: Box<Material>,
+    ior: f64,
+    distribution: Ggx,
+    tint: Rgb,
+}
+
+impl Coated {
+    /// Construct a coat with the given index of refraction and perceptually linear roughness in
+    /// [0, 1] over the base material. A zero roughness is a perfectly smooth coat.
+    pub fn new<M: Into<Material>>(base: M, ior: f64, roughness: f64) -> Self {
+        Self {
+            base: Box::new(base.into()),
+            ior,
+            distribution: Ggx::new(roughness),
+            tint: color::WHITE,
+        }
+    }
+
+    /// Set the color of white light after passing through the coat once at normal incidence.
+    ///
+    /// # Default:
+    /// white, the coat doesn't absorb light
+    pub fn tint(&mut self, tint: Rgb) -> &mut Self {
+        self.tint = tint;
+        self
+    }
+
+    /// The fraction of light passing through the coat to the base material and back, given the
+    /// cosines of the directions to the macro surface normal.
+    fn transmittance(&self, cos_o: f64, cos_i: f64) -> Rgb {
+        self.crossing_transmittance(cos_o) * self.crossing_transmittance(cos_i)
+    }
+
+    /// The fraction of light passing through the coat once, given the cosine of the direction to
+    /// the macro surface normal.
+    fn crossing_transmittance(&self, cos: f64) -> Rgb {
+        let cos = cos.abs();
+        if cos == 0.0 {
+            return color::BLACK;
+        }
+
+        let fresnel = 1.0 - microfacet::fresnel_dielectric(cos, self.ior);
+        let channel = |c: f64| c.max(0.0).powf(1.0 / cos);
+
+        fresnel
+            * Rgb::new(
+                channel(self.tint.r()),
+                channel(self.tint.g()),
+                channel(self.tint.b()),
+            )
+    }
+
+    /// The probability of sampling the coat instead of the base material.
+    fn coat_probability(&self, cos_o: f64) -> f64 {
+        microfacet::fresnel_dielectric(cos_o, self.ior)
+    }
+
+    fn scatter<R: Rng>(&self, rng: &mut R, ray: &Ray, record: &HitRecord) -> Option<Scatter> {
+        let onb = Onb::from_w(record.normal);
+        let wo = onb.world_to_local(-ray.direction().normalized());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        let coat = self.coat_probability(wo.z());
+
+        if rng.gen::<f64>() < coat {
+            if self.distribution.is_smooth() {
+                // the Fresnel reflectance cancels out with the probability of choosing the coat
+                let wi = microfacet::reflect(wo, Vec3::new(0.0, 0.0, 1.0));
+                return Some(Scatter::specular(onb.local_to_world(wi), color::WHITE));
+            }
+
+            let wh = self.distribution.sample_visible(wo, rng.gen(), rng.gen());
+            let wi = microfacet::reflect(wo, wh);
+            if wi.z() <= 0.0 {
+                return None;
+            }
+
+            let direction = onb.local_to_world(wi);
+            let pdf = self.pdf(ray, record, direction);
+            return Some(Scatter::sampled(
+                direction,
+                self.eval(ray, record, direction) / pdf,
+                pdf,
+            ));
+        }
+
+        let scatter = self.base.scatter(rng, ray, record)?;
+        let cos_i = record.normal.dot(scatter.direction.normalized());
+
+        if scatter.is_specular() {
+            let weight = self.transmittance(wo.z(), cos_i) / (1.0 - coat);
+            return Some(Scatter {
+                attenuation: weight * scatter.attenuation,
+                ..scatter
+            });
+        }
+
+        let pdf = self.pdf(ray, record, scatter.direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        let attenuation = self.eval(ray, record, scatter.direction) / pdf;
+        Some(Scatter {
+            attenuation,
+            lobe: Lobe::NonSpecular { pdf },
+            ..scatter
+        })
+    }
+
+    /// The reflection of the rough coat, zero for smooth coats.
+    fn eval_coat(&self, wo: Vec3, wi: Vec3) -> f64 {
+        if self.distribution.is_smooth() || wi.z() <= 0.0 {
+            return 0.0;
+        }
+
+        let wh = (wo + wi).normalized();
+        let reflectance = microfacet::fresnel_dielectric(wo.dot(wh), self.ior);
+        reflectance * self.distribution.d(wh) * self.distribution.g(wo, wi) / (4.0 * wo.z())
+    }
+
+    fn eval(&self, ray: &Ray, record: &HitRecord, direction: Vec3) -> Rgb {
+        let onb = Onb::from_w(record.normal);
+        let wo = onb.world_to_local(-ray.direction().normalized());
+        let wi = onb.world_to_local(direction.normalized());
+        if wo.z() <= 0.0 {
+            return color::BLACK;
+        }
+
+        let base = self.base.eval(ray, record, direction);
+        self.eval_coat(wo, wi) * color::WHITE + self.transmittance(wo.z(), wi.z()) * base
+    }
+
+    /// The radiance emitted by the base material after passing through the coat once.
+    fn emitted(&self, ray: &Ray, record: &HitRecord) -> Rgb {
+        let cos_o = record.normal.dot(-ray.direction().normalized());
+        self.crossing_transmittance(cos_o) * self.base.emitted(ray, record)
+    }
+
+    fn pdf(&self, ray: &Ray, record: &HitRecord, direction: Vec3) -> f64 {
+        let onb = Onb::from_w(record.normal);
+        let wo = onb.world_to_local(-ray.direction().normalized());
+        let wi = onb.world_to_local(direction.normalized());
+        if wo.z() <= 0.0 {
+            return 0.0;
+        }
+
+        let coat = self.coat_probability(wo.z());
+        let coat_pdf = if self.distribution.is_smooth() || wi.z() <= 0.0 {
+            0.0
+        } else {
+            let wh = (wo + wi).normalized();
+            self.distribution.pdf_visible(wo, wh) / (4.0 * wo.dot(wh))
+        };
+
+        coat * coat_pdf + (1.0 - coat) * self.base.pdf(ray, record, direction)
+    }
+}
+
+/// Subsurface scattering materials, e.g. skin, wax, marble or milk. Light entering the object
+/// takes a random walk through the homogeneous medium inside until it leaves the object again.
+/// Objects made of subsurface scattering materials must be closed.
+///
+/// The boundary reflects light specularly by the Fresnel reflectance, the transmitted light is
+/// diffused in both directions, so light sources can be sampled where the light leaves the
+/// object.
+///
+/// Every scattering event inside the medium counts as a bounce of the path, a low maximum depth
+/// of the integrator darkens objects with a short mean free path.
+pub struct Subsurface {
+    medium: Medium,
+    ir: f64,
+}
+
+impl Subsurface {
+    /// Construct a subsurface scattering material with the given index of refraction, the color
+    /// of a thick slab of the material and the mean free path of light of each color channel
+    /// inside the material in scene units.
+    pub fn new(albedo: Rgb, mean_free_path: Rgb, ir: f64) -> Self {
+        // invert the multiple scattering albedo to the single scattering albedo (Chiang, Kutz and
+        // Burley 2016, "Practical and Controllable Subsurface Scattering for Production Path
+        // Tracing")
+        let single_scattering = |a: f64| {
+            let a = a.clamp(0.0, 1.0);
+            1.0 - (4.09712 + 4.20863 * a - (9.59217 + 41.6808 * a + 17.7126 * a * a).sqrt()).powi(2)
+        };
+
+        Self {
+            medium: Medium::new(
+                Rgb::new(
+                    single_scattering(albedo.r()),
+                    single_scattering(albedo.g()),
+                    single_scattering(albedo.b()),
+                ),
+                mean_free_path,
+            ),
+            ir,
+        }
+    }
+
+    /// The probability of the light reflected by the boundary, light inside is never reflected.
+    fn reflectance(&self, ray: &Ray, record: &HitRecord) -> f64 {
+        match record.pointing {
+            Pointing::Inward => 0.0,
+            Pointing::Outward => {
+                let cos_i = -ray.direction().normalized().dot(record.normal);
+                microfacet::fresnel_dielectric(cos_i, self.ir)
+            }
+        }
+    }
+
+    fn scatter<R: Rng>(&self, rng: &mut R, ray: &Ray, record: &HitRecord) -> Scatter {
+        let reflectance = self.reflectance(ray, record);
+        if rng.gen::<f64>() < reflectance {
+            let direction = reflect(ray.direction().normalized(), record.normal);
+            return Scatter::specular(direction, color::WHITE);
+        }
+
+        // diffuse transmission through the boundary
+        let onb = Onb::from_w(-record.normal);
+        let local = sampling::random_cosine_direction(rng);
+        let pdf = (1.0 - reflectance) * sampling::cosine_pdf(local.z());
+        let scatter = Scatter::sampled(onb.local_to_world(local), color::WHITE, pdf);
+
+        match record.pointing {
+            Pointing::Outward => scatter.with_medium(self.medium),
+            Pointing::Inward => scatter,
+        }
+    }
+
+    fn eval(&self, ray: &Ray, record: &HitRecord, direction: Vec3) -> Rgb {
+        self.pdf(ray, record, direction) * color::WHITE
+    }
+
+    fn pdf(&self, ray: &Ray, record: &HitRecord, direction: Vec3) -> f64 {
+        let cos_t = -record.normal.dot(direction.normalized());
+        (1.0 - self.reflectance(ray, record)) * sampling::cosine_pdf(cos_t)
+    }
+}
+
+/// A material whose shading normal is perturbed by a normal map or a bump map, so flat geometry
+/// shows fine surface detail. The geometric normal is kept, light scattered to the other side of
+/// the true surface than the shading normal suggests is absorbed to avoid light leaks.
+pub struct NormalMapped {
+    base: Box<Material>,
+    map: SurfaceMap,
+}
+
+/// How the shading normal is perturbed.
+enum SurfaceMap {
+    /// Unit normals in tangent space encoded as colors, each channel maps [0, 1] to [-1, 1].
+    Normal(Texture),
+    /// Heights above the surface, the normal is tilted by the gradient of the height.
+    Bump { height: Texture, strength: f64 },
+}
+
+impl NormalMapped {
+    /// Perturb the shading normal of the base material by a tangent-space normal map, usually
+    /// loaded by [ImageTexture::open_linear](crate::texture::ImageTexture::open_linear). Red, green
+    /// and blue are the components along the tangent, the bitangent and the normal.
+    pub fn normal_map<M: Into<Material>, T: Into<Texture>>(base: M, normals: T) -> Self {
+        Self {
+            base: Box::new(base.into()),
+            map: SurfaceMap::Normal(normals.into()),
+        }
+    }
+
+    /// Perturb the shading normal of the base material by a scalar bump map, the strength scales
+    /// the gradient of the height with respect to the surface coordinates.
+    pub fn bump_map<M: Into<Material>, T: Into<Texture>>(
+        base: M,
+        height: T,
+        strength: f64,
+    ) -> Self {
+        Self {
+            base: Box::new(base.into()),
+            map: SurfaceMap::Bump {
+                height: height.into(),
+                strength,
+            },
+        }
+    }
+
+    /// The hit record with the perturbed shading normal.
+    fn shading_record(&self, record: &HitRecord) -> HitRecord {
+        let (u, v) = (record.u, record.v);
+        let (tangent, bitangent) = (record.tangent, record.bitangent());
+
+        let normal = match &self.map {
+            SurfaceMap::Normal(normals) => {
+                let n = normals.value(u, v);
+                let [x, y, z] = [n.r(), n.g(), n.b()].map(|c| 2.0 * c - 1.0);
+                x * tangent + y * bitangent + z.max(0.0) * record.normal
+            }
+            SurfaceMap::Bump { height, strength } => {
+                const DELTA: f64 = 1.0 / 1024.0;
+
+                let h = height.scalar(u, v);
+                let dhdu = (height.scalar(u + DELTA, v) - h) / DELTA;
+                let dhdv = (height.scalar(u, v + DELTA) - h) / DELTA;
+                record.normal - *strength * (dhdu * tangent + dhdv * bitangent)
+            }
+        };
+
+        if normal.norm_squared() == 0.0 {
+            return record.clone();
+        }
+
+        record.with_shading_normal(normal)
+    }
+
+    /// Return whether the direction is on the same side of the surface by both the geometric and
+    /// the shading normal.
+    fn same_side(record: &HitRecord, direction: Vec3) -> bool {
+        (record.geometric_normal.dot(direction) > 0.0) == (record.normal.dot(direction) > 0.0)
+    }
+
+    fn scatter<R: Rng>(&self, rng: &mut R, ray: &Ray, record: &HitRecord) -> Option<Scatter> {
+        let record = self.shading_record(record);
+        let scatter = self.base.scatter(rng, ray, &record)?;
+
+        if Self::same_side(&record, scatter.direction) {
+            Some(scatter)
+        } else {
+            None
+        }
+    }
+
+    fn eval(&self, ray: &Ray, record: &HitRecord, direction: Vec3) -> Rgb {
+        let record = self.shading_record(record);
+
+        if Self::same_side(&record, direction) {
+            self.base.eval(ray, &record, direction)
+        } else {
+            color::BLACK
+        }
+    }
+
+    fn pdf(&self, ray: &Ray, record: &HitRecord, direction: Vec3) -> f64 {
+        let record = self.shading_record(record);
+
+        if Self::same_side(&record, direction) {
+            self.base.pdf(ray, &record, direction)
+        } else {
+            0.0
+        }
+    }
+}
+
+/// A material with different looks on the front and the back of the surface, e.g. paper, leaves
+/// or lamp shades. The front is the outside of the object. A fraction of the light may pass
+/// diffusely to the other side of the surface.
+///
+/// Both materials see the surface as hit from their outside, so either side may be transmissive,
+/// e.g. glass.
+pub struct TwoSided {
+    front: Box<Material>,
+    back: Box<Material>,
+    transmission: f64,
+    transmission_color: Rgb,
+}
+
+impl TwoSided {
+    /// Construct a two-sided material from the materials of the front and the back.
+    pub fn new<F: Into<Material>, B: Into<Material>>(front: F, back: B) -> Self {
+        Self {
+            front: Box::new(front.into()),
+            back: Box::new(back.into()),
+            transmission: 0.0,
+            transmission_color: color::WHITE,
+        }
+    }
+
+    /// Set the fraction of light passing diffusely to the other side of the surface tinted by the
+    /// given color, the rest of the light is scattered by the material of the side hit.
+    ///
+    /// # Default:
+    /// 0, the surface is opaque
+    pub fn transmission(&mut self, fraction: f64, color: Rgb) -> &mut Self {
+        self.transmission = fraction.clamp(0.0, 1.0);
+        self.transmission_color = color;
+        self
+    }
+
+    /// The material of the side hit and the hit record as seen by it. The surface has no inside,
+    /// so both materials are hit from the outside, e.g. a dielectric back doesn't act as if light
+    /// left its interior.
+    fn side(&self, record: &HitRecord) -> (&Material, HitRecord) {
+        match record.pointing {
+            Pointing::Outward => (&self.front, record.clone()),
+            Pointing::Inward => (
+                &self.back,
+                HitRecord {
+                    pointing: Pointing::Outward,
+                    ..record.clone()
+                },
+            ),
+        }
+    }
+
+    /// The PDF of sampling the diffuse transmission.
+    fn transmission_pdf(record: &HitRecord, direction: Vec3) -> f64 {
+        sampling::cosine_pdf(-record.normal.dot(direction.normalized()))
+    }
+
+    fn scatter<R: Rng>(&self, rng: &mut R, ray: &Ray, record: &HitRecord) -> Option<Scatter> {
+        let direction = if rng.gen::<f64>() < self.transmission {
+            let local = sampling::random_cosine_direction(rng);
+            Onb::from_w(-record.normal).local_to_world(local)
+        } else {
+            let (side, side_record) = self.side(record);
+            let scatter = side.scatter(rng, ray, &side_record)?;
+            // the probabilities of choosing the side cancel out for delta distributions
+            if scatter.is_specular() {
+                return Some(scatter);
+            }
+
+            scatter.direction
+        };
+
+        let pdf = self.pdf(ray, record, direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        Some(Scatter::sampled(
+            direction,
+            self.eval(ray, record, direction) / pdf,
+            pdf,
+        ))
+    }
+
+    fn eval(&self, ray: &Ray, record: &HitRecord, direction: Vec3) -> Rgb {
+        let (side, side_record) = self.side(record);
+        let transmitted = Self::transmission_pdf(record, direction) * self.transmission_color;
+        self.transmission * transmitted
+            + (1.0 - self.transmission) * side.eval(ray, &side_record, direction)
+    }
+
+    fn pdf(&self, ray: &Ray, record: &HitRecord, direction: Vec3) -> f64 {
+        let (side, side_record) = self.side(record);
+        self.transmission * Self::transmission_pdf(record, direction)
+            + (1.0 - self.transmission) * side.pdf(ray, &side_record, direction)
+    }
+}
+
+/// A principled material in the style of the Disney BRDF (Burley 2012, "Physically-Based Shading
+/// at Disney"), blending a diffuse base with sheen, a specular lobe, a rough glass lobe and a
+/// clearcoat by intuitive parameters in [0, 1]. All parameters are textures.
+///
+/// The lobes are importance sampled by their approximate contribution, the sampled direction is
+/// weighted by the whole BSDF over the PDF of the mixture.
+///
+/// # Deviation from Disney
+/// The diffuse lobe is Lambertian without the retro-reflection term, the clearcoat uses the GGX
+/// distribution instead of GTR1 at a fixed roughness.
+pub struct Principled {
+    base_color: Texture,
+    metallic: Texture,
+    roughness: Texture,
+    specular: Texture,
+    specular_tint: Texture,
+    sheen: Texture,
+    clearcoat: Texture,
+    transmission: Texture,
+    ior: f64,
+}
+
+impl Principled {
+    /// The minimum roughness of the specular and the glass lobes, so they can always be
+    /// evaluated. Perfectly smooth surfaces are provided by [Metal](Metal) and
+    /// [Dielectric](Dielectric).
+    const MINIMUM_ROUGHNESS: f64 = 0.05;
+    /// The roughness of the clearcoat.
+    const CLEARCOAT_ROUGHNESS: f64 = 0.2;
+
+    /// Construct a principled material with the given base color, other parameters are set to
+    /// their defaults: a rough dielectric like plastic.
+    pub fn new<T: Into<Texture>>(base_color: T) -> Self {
+        Self {
+            base_color: base_color.into(),
+            metallic: 0.0.into(),
+            roughness: 0.5.into(),
+            specular: 0.5.into(),
+            specular_tint: 0.0.into(),
+            sheen: 0.0.into(),
+            clearcoat: 0.0.into(),
+            transmission: 0.0.into(),
+            ior: 1.5,
+        }
+    }
+
+    /// Set how metallic the material is, metals have no diffuse lobe and reflect light tinted by
+    /// the base color.
+    ///
+    /// # Default:
+    /// 0
+    pub fn metallic<T: Into<Texture>>(&mut self, metallic: T) -> &mut Self {
+        self.metallic = metallic.into();
+        self
+    }
+
+    /// Set the perceptually linear roughness of the specular and the glass lobes.
+    ///
+    /// # Default:
+    /// 0.5
+    pub fn roughness<T: Into<Texture>>(&mut self, roughness: T) -> &mut Self {
+        self.roughness = roughness.into();
+        self
+    }
+
+    /// Set the strength of the specular reflection of the non-metallic part, 0.5 is a
+    /// reflectance of 4% at normal incidence.
+    ///
+    /// # Default:
+    /// 0.5
+    pub fn specular<T: Into<Texture>>(&mut self, specular: T) -> &mut Self {
+        self.specular = specular.into();
+        self
+    }
+
+    /// Set how much the specular reflection of the non-metallic part is tinted by the base color.
+    ///
+    /// # Default:
+    /// 0
+    pub fn specular_tint<T: Into<Texture>>(&mut self, specular_tint: T) -> &mut Self {
+        self.specular_tint = specular_tint.into();
+        self
+    }
+
+    /// Set the strength of the sheen at grazing angles, e.g. of cloth.
+    ///
+    /// # Default:
+    /// 0
+    pub fn sheen<T: Into<Texture>>(&mut self, sheen: T) -> &mut Self {
+        self.sheen = sheen.into();
+        self
+    }
+
+    /// Set the strength of a glossy clear coat on top of the material, e.g. car paint.
+    ///
+    /// # Default:
+    /// 0
+    pub fn clearcoat<T: Into<Texture>>(&mut self, clearcoat: T) -> &mut Self {
+        self.clearcoat = clearcoat.into();
+        self
+    }
+
+    /// Set how much of the non-metallic part transmits light like glass instead of scattering it
+    /// diffusely.
+    ///
+    /// # Default:
+    /// 0
+    pub fn transmission<T: Into<Texture>>(&mut self, transmission: T) -> &mut Self {
+        self.transmission = transmission.into();
+        self
+    }
+
+    /// Set the index of refraction of the transmitting part.
+    ///
+    /// # Default:
+    /// 1.5
+    pub fn ior(&mut self, ior: f64) -> &mut Self {
+        self.ior = ior;
+        self
+    }
+
+    /// Evaluate the textures at the hit point.
+    fn lobes(&self, ray: &Ray, record: &HitRecord) -> PrincipledLobes {
+        let (u, v) = (record.u, record.v);
+        let base_color = self.base_color.value(u, v);
+        let metallic = self.metallic.scalar(u, v).clamp(0.0, 1.0);
+        let roughness = self
+            .roughness
+            .scalar(u, v)
+            .clamp(Self::MINIMUM_ROUGHNESS, 1.0);
+        let transmission = self.transmission.scalar(u, v).clamp(0.0, 1.0);
+
+        let luminance = base_color.luminance();
+        let tint = if luminance > 0.0 {
+            base_color / luminance
+        } else {
+            color::WHITE
+        };
+
+        let specular_tint = self.specular_tint.scalar(u, v).clamp(0.0, 1.0);
+        let dielectric_f0 = (0.08 * self.specular.scalar(u, v).max(0.0))
+            * ((1.0 - specular_tint) * color::WHITE + specular_tint * tint);
+
+        let onb = Onb::from_w(record.normal);
+        let wo = onb.world_to_local(-ray.direction().normalized());
+
+        PrincipledLobes {
+            base_color,
+            f0: (1.0 - metallic) * dielectric_f0 + metallic * base_color,
+            sheen: self.sheen.scalar(u, v).max(0.0),
+            clearcoat: self.clearcoat.scalar(u, v).max(0.0),
+            diffuse_weight: (1.0 - metallic) * (1.0 - transmission),
+            glass_weight: (1.0 - metallic) * transmission,
+            glass: RoughDielectric::new(self.ior, roughness),
+            distribution: Ggx::new(roughness),
+            clearcoat_distribution: Ggx::new(Self::CLEARCOAT_ROUGHNESS),
+            inside: record.pointing == Pointing::Inward,
+            onb,
+            wo,
+        }
+    }
+
+    fn scatter<R: Rng>(&self, rng: &mut R, ray: &Ray, record: &HitRecord) -> Option<Scatter> {
+        let lobes = self.lobes(ray, record);
+        let wo = lobes.wo;
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        let probabilities = lobes.probabilities();
+        let mut choice = rng.gen::<f64>();
+        let lobe = probabilities
+            .iter()
+            .position(|&p| {
+                choice -= p;
+                choice < 0.0
+            })
+            .unwrap_or(PrincipledLobes::GLASS);
+
+        let wi = match lobe {
+            PrincipledLobes::DIFFUSE => sampling::random_cosine_direction(rng),
+            PrincipledLobes::SPECULAR => {
+                let wh = lobes.distribution.sample_visible(wo, rng.gen(), rng.gen());
+                microfacet::reflect(wo, wh)
+            }
+            PrincipledLobes::CLEARCOAT => {
+                let wh = lobes
+                    .clearcoat_distribution
+                    .sample_visible(wo, rng.gen(), rng.gen());
+                microfacet::reflect(wo, wh)
+            }
+            _ => {
+                let scatter = lobes.glass.scatter(rng, ray, record)?;
+                lobes.onb.world_to_local(scatter.direction)
+            }
+        };
+
+        let direction = lobes.onb.local_to_world(wi);
+        let pdf = lobes.pdf(ray, record, wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        Some(Scatter::sampled(
+            direction,
+            lobes.eval(ray, record, wi) / pdf,
+            pdf,
+        ))
+    }
+
+    fn eval(&self, ray: &Ray, record: &HitRecord, direction: Vec3) -> Rgb {
+        let lobes = self.lobes(ray, record);
+        let wi = lobes.onb.world_to_local(direction.normalized());
+        lobes.eval(ray, record, wi)
+    }
+
+    fn pdf(&self, ray: &Ray, record: &HitRecord, direction: Vec3) -> f64 {
+        let lobes = self.lobes(ray, record);
+        let wi = lobes.onb.world_to_local(direction.normalized());
+        lobes.pdf(ray, record, wi)
+    }
+}
+
+/// The parameters of a [Principled](Principled) material evaluated at a hit point, directions are
+/// in the local shading space.
+struct PrincipledLobes {
+    base_color: Rgb,
+    /// Reflectance of the specular lobe at normal incidence.
+    f0: Rgb,
+    sheen: f64,
+    clearcoat: f64,
+    diffuse_weight: f64,
+    glass_weight: f64,
+    glass: RoughDielectric,
+    distribution: Ggx,
+    clearcoat_distribution: Ggx,
+    /// Light inside the material only sees the glass interface.
+    inside: bool,
+    onb: Onb,
+    wo: Vec3,
+}
+
+impl PrincipledLobes {
+    const DIFFUSE: usize = 0;
+    const SPECULAR: usize = 1;
+    const CLEARCOAT: usize = 2;
+    const GLASS: usize = 3;
+
+    /// The probabilities of sampling each lobe, roughly proportional to their contribution.
+    fn probabilities(&self) -> [f64; 4] {
+        if self.inside {
+            let glass = if self.glass_weight > 0.0 { 1.0 } else { 0.0 };
+            return [0.0, 0.0, 0.0, glass];
+        }
+
+        let cos_o = self.wo.z();
+        let weights = [
+            self.diffuse_weight * self.base_color.luminance().max(0.1),
+            (1.0 - self.glass_weight) * schlick(self.f0, cos_o).luminance(),
+            0.25 * self.clearcoat * schlick(Rgb::new(0.04, 0.04, 0.04), cos_o).r(),
+            self.glass_weight,
+        ];
+
+        let sum = weights.iter().sum::<f64>();
+        if sum <= 0.0 {
+            return [1.0, 0.0, 0.0, 0.0];
+        }
+
+        weights.map(|w| w / sum)
+    }
+
+    /// The BSDF times the cosine term of the whole material.
+    fn eval(&self, ray: &Ray, record: &HitRecord, wi: Vec3) -> Rgb {
+        let wo = self.wo;
+        if wo.z() <= 0.0 {
+            return color::BLACK;
+        }
+
+        let mut f = color::BLACK;
+
+        if self.glass_weight > 0.0 {
+            let direction = self.onb.local_to_world(wi);
+            let glass = self.glass.eval(ray, record, direction);
+            // light entering the material is tinted once by the base color
+            let tint = if wi.z() < 0.0 && !self.inside {
+                self.base_color
+            } else {
+                color::WHITE
+            };
+            let weight = if self.inside { 1.0 } else { self.glass_weight };
+            f += weight * (glass * tint);
+        }
+
+        if self.inside || wi.z() <= 0.0 {
+            return f;
+        }
+
+        let wh = (wo + wi).normalized();
+        let cos_d = wi.dot(wh);
+
+        // diffuse and sheen
+        let sheen = self.sheen * (1.0 - cos_d).max(0.0).powi(5);
+        f += (self.diffuse_weight * wi.z())
+            * (std::f64::consts::FRAC_1_PI * self.base_color + sheen * color::WHITE);
+
+        // specular reflection, the glass lobe has its own reflection
+        let specular = self.distribution.d(wh) * self.distribution.g(wo, wi) / (4.0 * wo.z());
+        f += ((1.0 - self.glass_weight) * specular) * schlick(self.f0, cos_d);
+
+        // clearcoat
+        let clearcoat = self.clearcoat_distribution.d(wh) * self.clearcoat_distribution.g(wo, wi)
+            / (4.0 * wo.z());
+        f += (0.25 * self.clearcoat * clearcoat) * schlick(Rgb::new(0.04, 0.04, 0.04), cos_d);
+
+        f
+    }
+
+    /// The PDF of sampling `wi` from the mixture of lobes.
+    fn pdf(&self, ray: &Ray, record: &HitRecord, wi: Vec3) -> f64 {
+        let wo = self.wo;
+        if wo.z() <= 0.0 {
+            return 0.0;
+        }
+
+        let [diffuse, specular, clearcoat, glass] = self.probabilities();
+        let mut pdf = 0.0;
+
+        if glass > 0.0 {
+            pdf += glass * self.glass.pdf(ray, record, self.onb.local_to_world(wi));
+        }
+
+        if wi.z() > 0.0 {
+            let wh = (wo + wi).normalized();
+            let cos_h = wo.dot(wh);
+
+            pdf += diffuse * sampling::cosine_pdf(wi.z());
+            pdf += specular * self.distribution.pdf_visible(wo, wh) / (4.0 * cos_h);
+            pdf += clearcoat * self.clearcoat_distribution.pdf_visible(wo, wh) / (4.0 * cos_h);
+        }
+
+        pdf
+    }
+}
+
+/// The Schlick approximation of the Fresnel reflectance with the given reflectance at normal
+/// incidence.
+fn schlick(f0: Rgb, cos_i: f64) -> Rgb {
+    let weight = (1.0 - cos_i.clamp(0.0, 1.0)).powi(5);
+    (1.0 - weight) * f0 + weight * color::WHITE
+}
+
+fn reflect(direction: Vec3, normal: Vec3) -> Vec3 {
+    direction - 2.0 * direction.dot(normal) * normal
+}
+
+/// Reflectance of a surface covered by a thin film per color channel, or at the single wavelength
+/// of the light as a gray color. `eta` and `k` are the complex index of refraction of the
+/// substrate.
+fn film_reflectance(
+    film: &ThinFilm,
+    cos_i: f64,
+    outside: f64,
+    eta: Rgb,
+    k: Rgb,
+    wavelength: Option<f64>,
+) -> Rgb {
+    match wavelength {
+        Some(wavelength) => {
+            let eta = spectrum::rgb_to_spectrum(eta, wavelength);
+            let k = spectrum::rgb_to_spectrum(k, wavelength);
+            let r = film.reflectance(cos_i, outside, eta, k, wavelength);
+            Rgb::new(r, r, r)
+        }
+        None => Rgb::new(
+            film.reflectance(cos_i, outside, eta.r(), k.r(), 650.0),
+            film.reflectance(cos_i, outside, eta.g(), k.g(), 550.0),
+            film.reflectance(cos_i, outside, eta.b(), k.b(), 450.0),
+        ),
+    }
+}
+
+/// The complex index of refraction `eta + i k` of a metal with the given reflectance at normal
+/// incidence, using the reflectance as the edge tint as well (Gulbrandsen 2014).
+fn artist_friendly_ior(reflectance: f64) -> (f64, f64) {
+    let r = reflectance.clamp(0.0, 0.99);
+    let g = r;
+    let sqrt_r = r.sqrt();
+
+    let eta = g * (1.0 - r) / (1.0 + r) + (1.0 - g) * (1.0 + sqrt_r) / (1.0 - sqrt_r);
+    let k2 = (r * (eta + 1.0).powi(2) - (eta - 1.0).powi(2)) / (1.0 - r);
+    (eta, k2.max(0.0).sqrt())
+}
+
+fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
+    let r0 = ((1.0 - ref_idx) / (1.0 + ref_idx)).powi(2);
+    r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+}
+
+impl Distribution<Lambertian> for Standard {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Lambertian {
+        Lambertian::new(rng.gen::<Rgb>() * rng.gen::<Rgb>())
+    }
+}
+
+impl Distribution<Metal> for Standard {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Metal {
+        let albedo = Rgb::new(
+            rng.gen_range(0.5..1.0),
+            rng.gen_range(0.5..1.0),
+            rng.gen_range(0.5..1.0),
+        );
+        let fuzz = rng.gen_range(0.0..0.5);
+
+        Metal::new(albedo, fuzz)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{
+        hittable::{Hittable, Quad},
+        texture::Checker,
+    };
+
+    /// Hit a unit sphere with a few rays, check that the attenuation of every sampled
+    /// non-specular direction matches the evaluated BSDF over the PDF.
+    fn assert_consistent(material: &Material) {
+        let mut rng = rand::thread_rng();
+
+        for i in 0..40 {
+            // half of the rays hit the sphere from the outside, the other half from the inside
+            let ray = if i % 2 == 0 {
+                let origin = Sphere::unit()
+                    .random_point_on_surface(&mut rng)
+                    .stretch(3.0);
+                Ray::new(origin, -origin)
+            } else {
+                let origin = 0.5 * Sphere::unit().random_point_in_sphere(&mut rng);
+                Ray::new(origin, Sphere::unit().random_point_on_surface(&mut rng))
+            };
+            let record = Sphere::unit().hit(&ray, 0.0, f64::INFINITY).unwrap();
+
+            for _ in 0..20 {
+                let scatter = match material.scatter(&mut rng, &ray, &record) {
+                    Some(scatter) => scatter,
+                    None => continue,
+                };
+
+                let pdf = match scatter.pdf() {
+                    Some(pdf) => pdf,
+                    None => continue,
+                };
+
+                let expected = material.eval(&ray, &record, scatter.direction) / pdf;
+                assert!((*expected - *scatter.attenuation).norm() < 1e-6);
+                assert!((material.pdf(&ray, &record, scatter.direction) - pdf).abs() < 1e-6 * pdf);
+            }
+        }
+    }
+
+    #[test]
+    fn lambertian_consistent() {
+        assert_consistent(&Lambertian::new(Rgb::new(0.2, 0.5, 0.8)).into());
+    }
+
+    #[test]
+    fn oren_nayar_consistent() {
+        assert_consistent(&OrenNayar::new(Rgb::new(0.7, 0.6, 0.5), 20.0).into());
+
+        // zero roughness is Lambertian
+        let ray = Ray::new(Vec3::new(0.3, 0.2, 3.0), Vec3::new(-0.1, 0.0, -1.0));
+        let record = Sphere::unit().hit(&ray, 0.0, f64::INFINITY).unwrap();
+        let direction = Vec3::new(0.5, 0.5, 1.0);
+        let albedo = Rgb::new(0.5, 0.5, 0.5);
+        let smooth = OrenNayar::new(albedo, 0.0).eval(&ray, &record, direction);
+        let lambertian = Lambertian::new(albedo).eval(&record, direction);
+        assert!((*smooth - *lambertian).norm() < 1e-9);
+    }
+
+    #[test]
+    fn conductor_consistent() {
+        assert_consistent(&Conductor::gold(0.3).into());
+        assert_consistent(&Conductor::aluminium(0.8).into());
+    }
+
+    #[test]
+    fn dielectric_absorption() {
+        let mut rng = rand::thread_rng();
+        let color = Rgb::new(0.5, 0.8, 0.9);
+        let glass = Material::from(Dielectric::tinted(1.5, color, 2.0));
+        let sphere = Sphere {
+            center: Vec3::origin(),
+            radius: 2.0,
+        };
+
+        // travelled the given distance inside the glass
+        let ray = Ray::new(Vec3::origin(), Vec3::new(0.0, 0.0, 0.5));
+        let record = sphere.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        let scatter = glass.scatter(&mut rng, &ray, &record).unwrap();
+        assert!((*scatter.attenuation - *color).norm() < 1e-9);
+
+        // entering the glass from the outside
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 4.0), Vec3::new(0.0, 0.0, -1.0));
+        let record = sphere.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        let scatter = glass.scatter(&mut rng, &ray, &record).unwrap();
+        assert!((*scatter.attenuation - *color::WHITE).norm() < 1e-9);
+
+        // rough glass absorbs the same, relative to clear rough glass
+        let rough = Material::from(RoughDielectric::tinted(1.5, 0.3, color, 2.0));
+        let clear = Material::from(RoughDielectric::new(1.5, 0.3));
+        assert_consistent(&rough);
+
+        let direction = Vec3::new(0.1, 0.2, 1.0);
+        let inside = Ray::new(Vec3::origin(), Vec3::new(0.0, 0.0, 0.5));
+        let record = sphere.hit(&inside, 0.0, f64::INFINITY).unwrap();
+        let expected = color * clear.eval(&inside, &record, direction);
+        assert!((*rough.eval(&inside, &record, direction) - *expected).norm() < 1e-9);
+
+        let record = sphere.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        let expected = clear.eval(&ray, &record, -direction);
+        assert!((*rough.eval(&ray, &record, -direction) - *expected).norm() < 1e-9);
+    }
+
+    #[test]
+    fn rough_dielectric_consistent() {
+        assert_consistent(&RoughDielectric::new(1.5, 0.3).into());
+        assert_consistent(&RoughDielectric::new(1.33, 0.9).into());
+    }
+
+    #[test]
+    fn principled_consistent() {
+        let plastic = Principled::new(Rgb::new(0.8, 0.2, 0.1));
+        assert_consistent(&plastic.into());
+
+        let mut metal = Principled::new(Rgb::new(0.9, 0.6, 0.3));
+        metal.metallic(1.0).roughness(0.2).clearcoat(1.0);
+        assert_consistent(&metal.into());
+
+        let mut glass = Principled::new(color::WHITE);
+        glass
+            .transmission(0.8)
+            .roughness(0.3)
+            .sheen(0.5)
+            .specular_tint(Checker::new(0.0, 1.0, 8.0));
+        assert_consistent(&glass.into());
+    }
+
+    #[test]
+    fn coated_consistent() {
+        let mut varnish = Coated::new(Lambertian::new(Rgb::new(0.6, 0.3, 0.1)), 1.5, 0.0);
+        varnish.tint(Rgb::new(0.9, 0.8, 0.6));
+        assert_consistent(&varnish.into());
+
+        let paint = Coated::new(Conductor::copper(0.4), 1.5, 0.2);
+        assert_consistent(&paint.into());
+
+        // a coat over a black base reflects at most as much as the coat interface
+        let mut rng = rand::thread_rng();
+        let coat = Material::from(Coated::new(Lambertian::new(color::BLACK), 1.5, 0.0));
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 3.0), Vec3::new(0.0, 0.0, -1.0));
+        let record = Sphere::unit().hit(&ray, 0.0, f64::INFINITY).unwrap();
+        let reflected = (0..1000)
+            .filter_map(|_| coat.scatter(&mut rng, &ray, &record))
+            .map(|scatter| scatter.attenuation.r())
+            .sum::<f64>()
+            / 1000.0;
+        assert!(reflected < 0.1, "{}", reflected);
+    }
+
+    #[test]
+    fn thin_film_energy() {
+        let mut rng = rand::thread_rng();
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 3.0), Vec3::new(0.0, 0.0, -1.0));
+        let record = Sphere::unit().hit(&ray, 0.0, f64::INFINITY).unwrap();
+
+        // a film without thickness keeps the color of the metal at normal incidence
+        let albedo = Rgb::new(0.9, 0.6, 0.3);
+        let metal = Material::from(Metal::new(albedo, 0.0).with_thin_film(0.0, 1.5));
+        let scatter = metal.scatter(&mut rng, &ray, &record).unwrap();
+        assert!((*scatter.attenuation - *albedo).norm() < 1e-6);
+
+        // a bubble neither absorbs nor creates light
+        const N: usize = 10_000;
+        let bubble = Material::from(Dielectric::new(1.0).with_thin_film(500.0, 1.33));
+        let mean = (0..N)
+            .map(|_| bubble.scatter(&mut rng, &ray, &record).unwrap().attenuation)
+            .fold(Rgb::default(), |acc, rgb| acc + rgb)
+            / N as f64;
+        assert!((*mean - *color::WHITE).norm() < 0.05);
+    }
+
+    #[test]
+    fn subsurface_consistent() {
+        assert_consistent(
+            &Subsurface::new(Rgb::new(0.8, 0.5, 0.4), Rgb::new(0.1, 0.05, 0.02), 1.4).into(),
+        );
+    }
+
+    #[test]
+    fn normal_mapped_consistent() {
+        let base = || Lambertian::new(Rgb::new(0.5, 0.5, 0.5));
+        let flat = Material::from(NormalMapped::normal_map(base(), Rgb::new(0.5, 0.5, 1.0)));
+        let tilted = Material::from(NormalMapped::normal_map(base(), Rgb::new(0.8, 0.4, 0.8)));
+        let bumpy = Material::from(NormalMapped::bump_map(
+            base(),
+            Checker::new(0.0, 1.0, 16.0),
+            0.01,
+        ));
+        assert_consistent(&tilted);
+        assert_consistent(&bumpy);
+
+        // a flat normal map doesn't change the material
+        let ray = Ray::new(Vec3::new(0.3, 0.2, 3.0), Vec3::new(-0.1, 0.0, -1.0));
+        let record = Sphere::unit().hit(&ray, 0.0, f64::INFINITY).unwrap();
+        let direction = Vec3::new(0.5, 0.5, 1.0);
+        let expected = Material::from(base()).eval(&ray, &record, direction);
+        assert!((*flat.eval(&ray, &record, direction) - *expected).norm() < 1e-9);
+
+        // a tilted normal map does
+        assert!((*tilted.eval(&ray, &record, direction) - *expected).norm() > 1e-3);
+
+        // directions absorbed for leaking through the true surface are never sampled
+        let mut rng = rand::thread_rng();
+        for _ in 0..1000 {
+            let direction = Sphere::unit().random_point_on_surface(&mut rng);
+            if tilted.eval(&ray, &record, direction).norm() == 0.0 {
+                assert_eq!(tilted.pdf(&ray, &record, direction), 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn two_sided() {
+        let mut rng = rand::thread_rng();
+        let (front, back) = (Rgb::new(0.9, 0.1, 0.1), Rgb::new(0.1, 0.1, 0.9));
+        let paper = Material::from(TwoSided::new(Lambertian::new(front), Lambertian::new(back)));
+
+        let outside = Ray::new(Vec3::new(0.0, 0.0, 3.0), Vec3::new(0.0, 0.0, -1.0));
+        let record = Sphere::unit().hit(&outside, 0.0, f64::INFINITY).unwrap();
+        let scatter = paper.scatter(&mut rng, &outside, &record).unwrap();
+        assert!((*scatter.attenuation - *front).norm() < 1e-9);
+
+        let inside = Ray::new(Vec3::origin(), Vec3::new(0.0, 0.0, 1.0));
+        let record = Sphere::unit().hit(&inside, 0.0, f64::INFINITY).unwrap();
+        let scatter = paper.scatter(&mut rng, &inside, &record).unwrap();
+        assert!((*scatter.attenuation - *back).norm() < 1e-9);
+
+        let mut lamp_shade = TwoSided::new(Lambertian::new(front), OrenNayar::new(back, 20.0));
+        lamp_shade.transmission(0.4, Rgb::new(1.0, 0.8, 0.5));
+        assert_consistent(&lamp_shade.into());
+        assert_consistent(&TwoSided::new(Metal::new(front, 0.0), Conductor::gold(0.3)).into());
+
+        // a glass back is entered from the outside, nothing is absorbed on the way to it
+        let glass = Dielectric::tinted(1.5, Rgb::new(0.5, 0.8, 0.9), 1.0);
+        let window = Material::from(TwoSided::new(Lambertian::new(front), glass));
+        let record = Sphere::unit().hit(&inside, 0.0, f64::INFINITY).unwrap();
+        for _ in 0..100 {
+            let scatter = window.scatter(&mut rng, &inside, &record).unwrap();
+            assert!((*scatter.attenuation - *color::WHITE).norm() < 1e-9);
+        }
+
+        // and bends light as if entering it, alike to glass hit from the front
+        let pane = Quad::new(
+            Vec3::new(-1.0, -1.0, 0.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+        );
+        let frosted = Material::from(TwoSided::new(
+            RoughDielectric::new(1.5, 0.3),
+            RoughDielectric::new(1.5, 0.3),
+        ));
+        let mirrored = |v: Vec3| Vec3::new(v.x(), v.y(), -v.z());
+        let direction = Vec3::new(0.2, 0.1, -1.0);
+        let front = Ray::new(Vec3::new(0.1, 0.2, 1.0), Vec3::new(0.5, 0.0, -1.0));
+        let back = Ray::new(mirrored(front.origin()), mirrored(front.direction()));
+        let front_record = pane.hit(&front, 0.0, f64::INFINITY).unwrap();
+        let back_record = pane.hit(&back, 0.0, f64::INFINITY).unwrap();
+        assert_eq!(back_record.pointing, Pointing::Inward);
+
+        let front_pdf = frosted.pdf(&front, &front_record, direction);
+        let back_pdf = frosted.pdf(&back, &back_record, mirrored(direction));
+        assert!(front_pdf > 0.0);
+        assert!((front_pdf - back_pdf).abs() < 1e-9 * front_pdf);
+    }
+
+    /// A Lambertian material defined through the public interface only.
+    struct CustomDiffuse(Rgb);
+
+    impl Scatterer for CustomDiffuse {
+        fn scatter(
+            &self,
+            mut rng: &mut dyn RngCore,
+            _ray: &Ray,
+            record: &HitRecord,
+        ) -> Option<Scatter> {
+            let local = sampling::random_cosine_direction(&mut rng);
+            let direction = Onb::from_w(record.normal).local_to_world(local);
+            Some(Scatter::sampled(
+                direction,
+                self.0,
+                sampling::cosine_pdf(local.z()),
+            ))
+        }
+
+        fn eval(&self, ray: &Ray, record: &HitRecord, direction: Vec3) -> Rgb {
+            self.pdf(ray, record, direction) * self.0
+        }
+
+        fn pdf(&self, _ray: &Ray, record: &HitRecord, direction: Vec3) -> f64 {
+            sampling::cosine_pdf(record.normal.dot(direction.normalized()))
+        }
+    }
+
+    #[test]
+    fn custom_consistent() {
+        assert_consistent(&Material::custom(CustomDiffuse(Rgb::new(0.3, 0.6, 0.9))));
+    }
+
+    /// A material glowing uniformly without reflecting any light.
+    pub(crate) struct Glow(pub(crate) Rgb);
+
+    impl Scatterer for Glow {
+        fn scatter(&self, _: &mut dyn RngCore, _: &Ray, _: &HitRecord) -> Option<Scatter> {
+            None
+        }
+
+        fn emitted(&self, _: &Ray, _: &HitRecord) -> Rgb {
+            self.0
+        }
+    }
+
+    #[test]
+    fn two_sided_emission() {
+        let radiance = Rgb::new(4.0, 3.0, 2.0);
+        let lamp = Material::from(TwoSided::new(
+            Material::custom(Glow(radiance)),
+            Lambertian::new(Rgb::new(0.5, 0.5, 0.5)),
+        ));
+
+        let outside = Ray::new(Vec3::new(0.0, 0.0, 3.0), Vec3::new(0.0, 0.0, -1.0));
+        let record = Sphere::unit().hit(&outside, 0.0, f64::INFINITY).unwrap();
+        assert!((*lamp.emitted(&outside, &record) - *radiance).norm() < 1e-9);
+
+        let inside = Ray::new(Vec3::origin(), Vec3::new(0.0, 0.0, 1.0));
+        let record = Sphere::unit().hit(&inside, 0.0, f64::INFINITY).unwrap();
+        assert_eq!(lamp.emitted(&inside, &record).norm(), 0.0);
+    }
+
+    #[test]
+    fn wrapped_emission() {
+        let radiance = Rgb::new(4.0, 3.0, 2.0);
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 3.0), Vec3::new(0.0, 0.0, -1.0));
+        let record = Sphere::unit().hit(&ray, 0.0, f64::INFINITY).unwrap();
+
+        let bumpy = Material::from(NormalMapped::bump_map(
+            Material::custom(Glow(radiance)),
+            0.5,
+            1.0,
+        ));
+        assert!((*bumpy.emitted(&ray, &record) - *radiance).norm() < 1e-9);
+
+        // light emitted under a coat is partly reflected back and absorbed by the tint
+        let tint = Rgb::new(0.9, 0.8, 0.7);
+        let mut coated = Coated::new(Material::custom(Glow(radiance)), 1.5, 0.2);
+        coated.tint(tint);
+        let expected = (1.0 - microfacet::fresnel_dielectric(1.0, 1.5)) * tint * radiance;
+        let emitted = Material::from(coated).emitted(&ray, &record);
+        assert!((*emitted - *expected).norm() < 1e-9);
     }
 }
@@ -0,0 +1,136 @@
+use rand::Rng;
+
+use crate::{color::Rgb, spectrum};
+
+/// A homogeneous participating medium scattering light isotropically, e.g. the inside of wax,
+/// marble or skin.
+#[derive(Debug, Clone, Copy)]
+pub struct Medium {
+    sigma_t: [f64; 3],
+    albedo: [f64; 3],
+}
+
+/// The result of sampling the distance a ray travels in a medium.
+pub struct MediumSample {
+    /// The distance travelled in the medium.
+    pub distance: f64,
+    /// Whether the ray is scattered by the medium at the distance, or passes through to the end
+    /// of the segment.
+    pub scattered: bool,
+    /// The transmittance times the scattering coefficient if scattered, over the PDF of
+    /// sampling the distance.
+    pub weight: Rgb,
+}
+
+impl Medium {
+    /// Construct a medium with the given single scattering albedo and mean free path per color
+    /// channel.
+    pub fn new(albedo: Rgb, mean_free_path: Rgb) -> Self {
+        Self {
+            sigma_t: [
+                extinction(mean_free_path.r()),
+                extinction(mean_free_path.g()),
+                extinction(mean_free_path.b()),
+            ],
+            albedo: [
+                albedo.r().clamp(0.0, 1.0),
+                albedo.g().clamp(0.0, 1.0),
+                albedo.b().clamp(0.0, 1.0),
+            ],
+        }
+    }
+
+    /// The medium seen by light of a single wavelength, all channels are uplifted to spectra and
+    /// evaluated at the wavelength. The medium is left as is if the wavelength is unknown.
+    pub fn at(&self, wavelength: Option<f64>) -> Self {
+        let wavelength = match wavelength {
+            Some(wavelength) => wavelength,
+            None => return *self,
+        };
+
+        let uplift = |[r, g, b]: [f64; 3]| spectrum::rgb_to_spectrum(Rgb::new(r, g, b), wavelength);
+
+        // mean free paths are uplifted instead of the coefficients, so a very dense channel
+        // doesn't swallow the others
+        let mean_free_path = uplift(self.sigma_t.map(|s| 1.0 / s));
+
+        Self {
+            sigma_t: [extinction(mean_free_path); 3],
+            albedo: [uplift(self.albedo); 3],
+        }
+    }
+
+    /// Sample the distance a ray travels in the medium before it's scattered, up to the distance
+    /// where the ray leaves the medium. One color channel is chosen to sample the distance, the
+    /// PDF is averaged over all channels.
+    pub fn sample<R: Rng>(&self, rng: &mut R, max_distance: f64) -> MediumSample {
+        let channel = rng.gen_range(0..3);
+        let distance = -(1.0 - rng.gen::<f64>()).ln() / self.sigma_t[channel];
+        let scattered = distance < max_distance;
+        let distance = distance.min(max_distance);
+
+        let transmittance = self.sigma_t.map(|s| (-s * distance).exp());
+        let density = |i: usize| {
+            if scattered {
+                self.sigma_t[i] * transmittance[i]
+            } else {
+                transmittance[i]
+            }
+        };
+        let pdf = (density(0) + density(1) + density(2)) / 3.0;
+
+        let weight = |i: usize| {
+            if pdf <= 0.0 {
+                0.0
+            } else if scattered {
+                transmittance[i] * self.sigma_t[i] * self.albedo[i] / pdf
+            } else {
+                transmittance[i] / pdf
+            }
+        };
+
+        MediumSample {
+            distance,
+            scattered,
+            weight: Rgb::new(weight(0), weight(1), weight(2)),
+        }
+    }
+}
+
+/// The extinction coefficient of the given mean free path, vanishing mean free paths are clamped
+/// to keep the coefficient finite.
+fn extinction(mean_free_path: f64) -> f64 {
+    1.0 / mean_free_path.max(1e-9)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn medium_transmittance() {
+        let mut rng = rand::thread_rng();
+        let medium = Medium::new(Rgb::new(1.0, 1.0, 1.0), Rgb::new(0.5, 1.0, 2.0));
+
+        // the expected weight of passing through a slab is its transmittance
+        const N: usize = 100_000;
+        let mut sum = Rgb::default();
+        for _ in 0..N {
+            let sample = medium.sample(&mut rng, 1.0);
+            if !sample.scattered {
+                sum += sample.weight;
+            }
+        }
+        let mean = sum / N as f64;
+
+        let expected = [2.0f64, 1.0, 0.5].map(|s| (-s).exp());
+        for (channel, expected) in [mean.r(), mean.g(), mean.b()].iter().zip(&expected) {
+            assert!(
+                (channel - expected).abs() < 0.02,
+                "{} {}",
+                channel,
+                expected
+            );
+        }
+    }
+}
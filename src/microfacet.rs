@@ -0,0 +1,390 @@
+use std::{
+    f64::consts::PI,
+    ops::{Add, Div, Mul, Sub},
+};
+
+use crate::Vec3;
+
+/// The Trowbridge-Reitz (GGX) microfacet distribution. All directions are in the local shading
+/// space, where the +z axis is the macro surface normal.
+#[derive(Debug, Clone, Copy)]
+pub struct Ggx {
+    alpha: f64,
+}
+
+impl Ggx {
+    /// The smallest alpha a distribution is allowed to have, smoother surfaces are treated as
+    /// perfectly smooth to avoid numerical issues.
+    const MINIMUM_ALPHA: f64 = 1e-3;
+
+    /// Construct a distribution from a perceptually linear roughness in [0, 1], alpha is the square
+    /// of the roughness.
+    pub fn new(roughness: f64) -> Self {
+        Self {
+            alpha: roughness.clamp(0.0, 1.0).powi(2),
+        }
+    }
+
+    /// The alpha parameter of the distribution.
+    pub fn alpha(&self) -> f64 {
+        self.alpha
+    }
+
+    /// Return whether the distribution is so narrow that it should be treated as a perfectly
+    /// smooth surface.
+    pub fn is_smooth(&self) -> bool {
+        self.alpha < Self::MINIMUM_ALPHA
+    }
+
+    /// The density of microfacets with the given normal.
+    pub fn d(&self, wh: Vec3) -> f64 {
+        if wh.z() <= 0.0 {
+            return 0.0;
+        }
+
+        let a2 = self.alpha * self.alpha;
+        let cos2 = wh.z() * wh.z();
+        let denominator = cos2 * (a2 - 1.0) + 1.0;
+        a2 / (PI * denominator * denominator)
+    }
+
+    /// The Smith auxiliary function.
+    fn lambda(&self, w: Vec3) -> f64 {
+        let cos2 = w.z() * w.z();
+        if cos2 == 0.0 {
+            return f64::INFINITY;
+        }
+
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        ((1.0 + self.alpha * self.alpha * tan2).sqrt() - 1.0) / 2.0
+    }
+
+    /// The Smith masking function, the fraction of microfacets visible from the given direction.
+    pub fn g1(&self, w: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// The height-correlated Smith masking-shadowing function, the fraction of microfacets
+    /// visible from both directions.
+    pub fn g(&self, wo: Vec3, wi: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Sample a microfacet normal from the distribution of normals visible from `wo` (Heitz 2018,
+    /// "Sampling the GGX Distribution of Visible Normals"), given two uniformly distributed
+    /// numbers in [0, 1). `wo` must be in the upper hemisphere.
+    pub fn sample_visible(&self, wo: Vec3, u1: f64, u2: f64) -> Vec3 {
+        // transform the view direction to the hemisphere configuration
+        let vh = Vec3::new(self.alpha * wo.x(), self.alpha * wo.y(), wo.z()).normalized();
+
+        // orthonormal basis around the view direction
+        let len2 = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if len2 > 0.0 {
+            Vec3::new(-vh.y(), vh.x(), 0.0) * len2.sqrt().recip()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = vh.cross(t1);
+
+        // parameterization of the projected area
+        let r = u1.sqrt();
+        let phi = 2.0 * PI * u2;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z());
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * r * phi.sin();
+
+        // reprojection onto the hemisphere
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+
+        // transform the normal back to the ellipsoid configuration
+        Vec3::new(self.alpha * nh.x(), self.alpha * nh.y(), nh.z().max(1e-6)).normalized()
+    }
+
+    /// The PDF of [sample_visible](Ggx::sample_visible) sampling the microfacet normal `wh`.
+    pub fn pdf_visible(&self, wo: Vec3, wh: Vec3) -> f64 {
+        if wo.z() <= 0.0 {
+            return 0.0;
+        }
+
+        self.g1(wo) * wo.dot(wh).max(0.0) * self.d(wh) / wo.z()
+    }
+}
+
+/// Reflect a direction pointing away from the surface about the normal.
+pub fn reflect(wo: Vec3, normal: Vec3) -> Vec3 {
+    -wo + 2.0 * wo.dot(normal) * normal
+}
+
+/// Refract a direction pointing away from the surface through the interface with the given normal
+/// on the same side, given the relative index of refraction (transmitted over incident). Return
+/// `None` in case of total internal reflection.
+pub fn refract(wo: Vec3, normal: Vec3, eta: f64) -> Option<Vec3> {
+    let cos_i = wo.dot(normal);
+    let sin2_t = (1.0 - cos_i * cos_i).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(-wo / eta + (cos_i / eta - cos_t) * normal)
+}
+
+/// Fresnel reflectance of a smooth interface between two dielectrics, given the cosine of the
+/// incident angle and the relative index of refraction (transmitted over incident).
+pub fn fresnel_dielectric(cos_i: f64, eta: f64) -> f64 {
+    let cos_i = cos_i.clamp(-1.0, 1.0);
+    let (cos_i, eta) = if cos_i < 0.0 {
+        (-cos_i, 1.0 / eta)
+    } else {
+        (cos_i, eta)
+    };
+
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        // total internal reflection
+        return 1.0;
+    }
+
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let r_parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    (r_parallel * r_parallel + r_perpendicular * r_perpendicular) / 2.0
+}
+
+/// Fresnel reflectance of a smooth interface between a dielectric and a conductor, given the
+/// cosine of the incident angle and the complex index of refraction `eta + i k` of the conductor
+/// relative to the dielectric.
+pub fn fresnel_conductor(cos_i: f64, eta: f64, k: f64) -> f64 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let eta = Complex::new(eta, k);
+
+    let sin2_i = 1.0 - cos_i * cos_i;
+    let sin2_t = Complex::from(sin2_i) / (eta * eta);
+    let cos_t = (Complex::from(1.0) - sin2_t).sqrt();
+
+    let cos_i = Complex::from(cos_i);
+    let r_parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    (r_parallel.norm_squared() + r_perpendicular.norm_squared()) / 2.0
+}
+
+/// A thin dielectric film on top of a surface, e.g. a soap bubble, an oil slick or the oxide layer
+/// of anodized metals. Light reflected by both sides of the film interferes, the reflectance
+/// oscillates with the wavelength and the angle of incidence.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThinFilm {
+    thickness: f64,
+    ior: f64,
+}
+
+impl ThinFilm {
+    /// Construct a film of the given thickness in nanometers and index of refraction.
+    pub fn new(thickness: f64, ior: f64) -> Self {
+        Self { thickness, ior }
+    }
+
+    /// Reflectance of light of the given wavelength in nanometers, given the cosine of the
+    /// incident angle, the index of refraction of the medium the light comes from and the complex
+    /// index of refraction `eta + i k` of the substrate under the film.
+    pub fn reflectance(&self, cos_i: f64, outside: f64, eta: f64, k: f64, lambda: f64) -> f64 {
+        let cos_i = cos_i.clamp(0.0, 1.0);
+        let (n0, n1, n2) = (
+            Complex::from(outside),
+            Complex::from(self.ior),
+            Complex::new(eta, k),
+        );
+
+        // Snell's law, the cosines become complex under total internal reflection or in
+        // absorbing media
+        let sin2_0 = Complex::from(1.0 - cos_i * cos_i);
+        let cos_0 = Complex::from(cos_i);
+        let cos_1 = (Complex::from(1.0) - n0 * n0 * sin2_0 / (n1 * n1)).sqrt();
+        let cos_2 = (Complex::from(1.0) - n0 * n0 * sin2_0 / (n2 * n2)).sqrt();
+
+        // phase difference between the light reflected by the top and the bottom of the film
+        let phase = Complex::from(4.0 * PI * self.thickness / lambda) * n1 * cos_1;
+        let shift = (Complex::new(0.0, 1.0) * phase).exp();
+
+        let s = |ni: Complex, ci: Complex, nj: Complex, cj: Complex| {
+            (ni * ci - nj * cj) / (ni * ci + nj * cj)
+        };
+        let p = |ni: Complex, ci: Complex, nj: Complex, cj: Complex| {
+            (nj * ci - ni * cj) / (nj * ci + ni * cj)
+        };
+        // the sum of all light bouncing inside the film
+        let airy = |r01: Complex, r12: Complex| {
+            ((r01 + r12 * shift) / (Complex::from(1.0) + r01 * r12 * shift)).norm_squared()
+        };
+
+        let r_s = airy(s(n0, cos_0, n1, cos_1), s(n1, cos_1, n2, cos_2));
+        let r_p = airy(p(n0, cos_0, n1, cos_1), p(n1, cos_1, n2, cos_2));
+        (r_s + r_p) / 2.0
+    }
+}
+
+/// A minimal complex number type for Fresnel equations.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    pub fn norm_squared(self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+
+    /// The exponential function.
+    pub fn exp(self) -> Self {
+        let scale = self.re.exp();
+        Self::new(scale * self.im.cos(), scale * self.im.sin())
+    }
+
+    /// The principal square root.
+    pub fn sqrt(self) -> Self {
+        let n = self.norm_squared().sqrt();
+        if n == 0.0 {
+            return Self::new(0.0, 0.0);
+        }
+
+        let t1 = (0.5 * (n + self.re.abs())).sqrt();
+        let t2 = 0.5 * self.im / t1;
+
+        if self.re >= 0.0 {
+            Self::new(t1, t2)
+        } else {
+            Self::new(t2.abs(), t1.copysign(self.im))
+        }
+    }
+}
+
+impl From<f64> for Complex {
+    fn from(re: f64) -> Self {
+        Self::new(re, 0.0)
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self::Output {
+        let scale = 1.0 / rhs.norm_squared();
+        Self::new(
+            scale * (self.re * rhs.re + self.im * rhs.im),
+            scale * (self.im * rhs.re - self.re * rhs.im),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+    use crate::{hittable::Sphere, sampling};
+
+    #[test]
+    fn ggx_normalized() {
+        let mut rng = rand::thread_rng();
+        let ggx = Ggx::new(0.5);
+
+        // the projected area of microfacets is the area of the macro surface
+        const N: usize = 200_000;
+        let integral = (0..N)
+            .map(|_| {
+                let wh = sampling::random_cosine_direction(&mut rng);
+                // cosine weighted sampling cancels out the projection
+                ggx.d(wh) * PI
+            })
+            .sum::<f64>()
+            / N as f64;
+
+        assert!((integral - 1.0).abs() < 0.05, "integral {}", integral);
+    }
+
+    #[test]
+    fn ggx_visible_normals() {
+        let mut rng = rand::thread_rng();
+        let ggx = Ggx::new(0.6);
+
+        for _ in 0..100 {
+            let mut wo = Sphere::unit().random_point_on_surface(&mut rng);
+            wo[2] = wo[2].abs().max(0.05);
+            let wo = wo.normalized();
+
+            let wh = ggx.sample_visible(wo, rng.gen(), rng.gen());
+            assert!((wh.norm() - 1.0).abs() < 1e-9);
+            assert!(wh.z() > 0.0);
+            assert!(wo.dot(wh) >= -1e-9);
+            assert!(ggx.pdf_visible(wo, wh) > 0.0);
+        }
+    }
+
+    #[test]
+    fn fresnel_limits() {
+        // normal incidence on glass
+        assert!((fresnel_dielectric(1.0, 1.5) - 0.04).abs() < 1e-9);
+        // grazing incidence always reflects
+        assert!((fresnel_dielectric(0.0, 1.5) - 1.0).abs() < 1e-9);
+        // total internal reflection from inside glass
+        assert_eq!(fresnel_dielectric(-0.2, 1.5), 1.0);
+
+        // a conductor without extinction behaves like a dielectric
+        assert!((fresnel_conductor(0.7, 1.5, 0.0) - fresnel_dielectric(0.7, 1.5)).abs() < 1e-9);
+        // normal incidence on a conductor: ((n - 1)^2 + k^2) / ((n + 1)^2 + k^2)
+        let expected = (0.5f64.powi(2) + 9.0) / (2.5f64.powi(2) + 9.0);
+        assert!((fresnel_conductor(1.0, 1.5, 3.0) - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn thin_film_interference() {
+        // a film without thickness doesn't change the reflectance
+        let film = ThinFilm::new(0.0, 1.33);
+        let bare = fresnel_dielectric(0.6, 1.5);
+        assert!((film.reflectance(0.6, 1.0, 1.5, 0.0, 550.0) - bare).abs() < 1e-9);
+        let bare = fresnel_conductor(0.6, 0.2, 3.9);
+        assert!((film.reflectance(0.6, 1.0, 0.2, 3.9, 550.0) - bare).abs() < 1e-9);
+
+        // a soap bubble reflects some colors much better than others
+        let bubble = ThinFilm::new(400.0, 1.33);
+        let reflectances = (400..700)
+            .step_by(10)
+            .map(|lambda| bubble.reflectance(1.0, 1.0, 1.0, 0.0, lambda as f64))
+            .collect::<Vec<_>>();
+        let max = reflectances.iter().cloned().fold(0.0, f64::max);
+        let min = reflectances.iter().cloned().fold(1.0, f64::min);
+        assert!(min < 0.01 && max > 0.05, "min {}, max {}", min, max);
+        assert!(reflectances.iter().all(|&r| (0.0..=1.0).contains(&r)));
+    }
+}
@@ -0,0 +1,622 @@
+use std::f64::consts::PI;
+
+use rand::Rng;
+
+use crate::{
+    hittable::{HitRecord, Hittable, HittableObject, Sampleable, SurfaceSample, AABB},
+    ray::Ray,
+    sampling::Onb,
+    Vec3,
+};
+
+/// A flat disk described by its center, the normal of its plane and its radius. The outward
+/// normal is the given normal, u is the angle around the normal and v runs from the rim to the
+/// center.
+pub struct Disk {
+    /// Center of the disk.
+    pub center: Vec3,
+    /// Normal of the plane of the disk.
+    pub normal: Vec3,
+    /// Radius of the disk.
+    pub radius: f64,
+}
+
+impl Disk {
+    /// Construct a disk around the center in the plane perpendicular to the normal.
+    pub fn new(center: Vec3, normal: Vec3, radius: f64) -> Self {
+        Self {
+            center,
+            normal,
+            radius,
+        }
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let frame = Frame::new(self.center, self.normal);
+        hit_annulus(&frame, ray, t_min, t_max, 0.0, 0.0, self.radius)
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        let r = self.radius;
+        Some(Frame::new(self.center, self.normal).bounding_box(-r, r, 0.0, 0.0))
+    }
+}
+
+impl Sampleable for Disk {
+    fn area(&self) -> f64 {
+        PI * self.radius.powi(2)
+    }
+
+    fn sample_surface<R: Rng>(&self, rng: &mut R) -> SurfaceSample {
+        let frame = Frame::new(self.center, self.normal);
+        sample_annulus(rng, &frame, 0.0, 0.0, self.radius)
+    }
+}
+
+/// A flat ring between two circles around the same center, e.g. a washer. Normals and surface
+/// coordinates are the same as the ones of a [Disk](Disk).
+pub struct Annulus {
+    /// Center of the annulus.
+    pub center: Vec3,
+    /// Normal of the plane of the annulus.
+    pub normal: Vec3,
+    /// Radius of the hole in the middle.
+    pub inner_radius: f64,
+    /// Radius of the rim.
+    pub outer_radius: f64,
+}
+
+impl Annulus {
+    /// Construct an annulus around the center in the plane perpendicular to the normal.
+    pub fn new(center: Vec3, normal: Vec3, inner_radius: f64, outer_radius: f64) -> Self {
+        assert!(0.0 <= inner_radius && inner_radius <= outer_radius);
+
+        Self {
+            center,
+            normal,
+            inner_radius,
+            outer_radius,
+        }
+    }
+}
+
+impl Hittable for Annulus {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let frame = Frame::new(self.center, self.normal);
+        hit_annulus(
+            &frame,
+            ray,
+            t_min,
+            t_max,
+            0.0,
+            self.inner_radius,
+            self.outer_radius,
+        )
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        let r = self.outer_radius;
+        Some(Frame::new(self.center, self.normal).bounding_box(-r, r, 0.0, 0.0))
+    }
+}
+
+impl Sampleable for Annulus {
+    fn area(&self) -> f64 {
+        PI * (self.outer_radius.powi(2) - self.inner_radius.powi(2))
+    }
+
+    fn sample_surface<R: Rng>(&self, rng: &mut R) -> SurfaceSample {
+        let frame = Frame::new(self.center, self.normal);
+        sample_annulus(rng, &frame, 0.0, self.inner_radius, self.outer_radius)
+    }
+}
+
+/// A cylinder between the centers of its base and top circles, e.g. a pipe or a table leg. u is
+/// the angle around the axis, v runs from the base to the top. Caps are disks with their own
+/// surface coordinates.
+pub struct Cylinder {
+    /// Center of the base circle.
+    pub base: Vec3,
+    /// Center of the top circle.
+    pub top: Vec3,
+    /// Radius of the cylinder.
+    pub radius: f64,
+    /// Whether the cylinder is closed by disks at both ends.
+    pub capped: bool,
+}
+
+impl Cylinder {
+    /// Construct an open cylinder between the centers of the base and the top circles.
+    pub fn new(base: Vec3, top: Vec3, radius: f64) -> Self {
+        Self {
+            base,
+            top,
+            radius,
+            capped: false,
+        }
+    }
+
+    /// Close the cylinder by disks at both ends.
+    pub fn capped(mut self) -> Self {
+        self.capped = true;
+        self
+    }
+
+    fn frame(&self) -> Frame {
+        Frame::new(self.base, self.top - self.base)
+    }
+
+    fn height(&self) -> f64 {
+        (self.top - self.base).norm()
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, ray: &Ray, t_min: f64, mut t_max: f64) -> Option<HitRecord> {
+        let frame = self.frame();
+        let (height, r) = (self.height(), self.radius);
+        let (o, d) = frame.ray(ray);
+
+        let a = d.x().powi(2) + d.y().powi(2);
+        let b = 2.0 * (o.x() * d.x() + o.y() * d.y());
+        let c = o.x().powi(2) + o.y().powi(2) - r.powi(2);
+
+        let mut closest = None;
+        if let Some((t0, t1)) = solve_quadratic(a, b, c) {
+            for t in IntoIterator::into_iter([t0, t1]) {
+                let p = o + t * d;
+                if t < t_min || t > t_max || !(0.0..=height).contains(&p.z()) {
+                    continue;
+                }
+
+                let normal = frame.to_world(Vec3::new(p.x(), p.y(), 0.0)).normalized();
+                let dpdu = frame.to_world(Vec3::new(-p.y(), p.x(), 0.0));
+                let uv = (azimuth(p), p.z() / height);
+                closest = Some(HitRecord::new(ray, t, normal, uv, dpdu));
+                t_max = t;
+                break;
+            }
+        }
+
+        if self.capped {
+            let flipped = Frame::new(self.base, self.base - self.top);
+            let caps = [(&flipped, 0.0), (&frame, height)];
+            for (frame, z) in IntoIterator::into_iter(caps) {
+                if let Some(record) = hit_annulus(frame, ray, t_min, t_max, z, 0.0, r) {
+                    t_max = record.t;
+                    closest = Some(record);
+                }
+            }
+        }
+
+        closest
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        let r = self.radius;
+        Some(self.frame().bounding_box(-r, r, 0.0, self.height()))
+    }
+}
+
+impl Sampleable for Cylinder {
+    fn area(&self) -> f64 {
+        let side = 2.0 * PI * self.radius * self.height();
+        if self.capped {
+            side + 2.0 * PI * self.radius.powi(2)
+        } else {
+            side
+        }
+    }
+
+    fn sample_surface<R: Rng>(&self, rng: &mut R) -> SurfaceSample {
+        let frame = self.frame();
+        let (height, r) = (self.height(), self.radius);
+
+        let side = 2.0 * PI * r * height;
+        let choice = rng.gen::<f64>() * self.area();
+        if choice >= side {
+            // the caps have equal areas
+            let cap = PI * r.powi(2);
+            return if choice - side < cap {
+                sample_annulus(rng, &Frame::new(self.base, -frame.onb.w()), 0.0, 0.0, r)
+            } else {
+                sample_annulus(rng, &frame, height, 0.0, r)
+            };
+        }
+
+        let phi = 2.0 * PI * rng.gen::<f64>();
+        let (x, y) = (r * phi.cos(), r * phi.sin());
+        SurfaceSample {
+            point: frame.to_world_point(Vec3::new(x, y, height * rng.gen::<f64>())),
+            normal: frame.to_world(Vec3::new(phi.cos(), phi.sin(), 0.0)),
+        }
+    }
+}
+
+/// A cone between the center of its base circle and its apex, e.g. a lamp shade or a spire. u is
+/// the angle around the axis, v runs from the base to the apex. The base cap is a disk with its own
+/// surface coordinates.
+pub struct Cone {
+    /// Center of the base circle.
+    pub base: Vec3,
+    /// The tip of the cone.
+    pub apex: Vec3,
+    /// Radius of the base circle.
+    pub radius: f64,
+    /// Whether the cone is closed by a disk at its base.
+    pub capped: bool,
+}
+
+impl Cone {
+    /// Construct a cone open at the base between the center of the base circle and the apex.
+    pub fn new(base: Vec3, apex: Vec3, radius: f64) -> Self {
+        Self {
+            base,
+            apex,
+            radius,
+            capped: false,
+        }
+    }
+
+    /// Close the cone by a disk at its base.
+    pub fn capped(mut self) -> Self {
+        self.capped = true;
+        self
+    }
+
+    fn frame(&self) -> Frame {
+        Frame::new(self.base, self.apex - self.base)
+    }
+
+    fn height(&self) -> f64 {
+        (self.apex - self.base).norm()
+    }
+
+    fn side_area(&self) -> f64 {
+        PI * self.radius * self.radius.hypot(self.height())
+    }
+}
+
+impl Hittable for Cone {
+    fn hit(&self, ray: &Ray, t_min: f64, mut t_max: f64) -> Option<HitRecord> {
+        let frame = self.frame();
+        let height = self.height();
+        let (o, d) = frame.ray(ray);
+
+        // the radius of the cone shrinks linearly by the slope towards the apex
+        let slope = self.radius / height;
+        let k2 = slope.powi(2);
+        let a = d.x().powi(2) + d.y().powi(2) - k2 * d.z().powi(2);
+        let b = 2.0 * (o.x() * d.x() + o.y() * d.y() + k2 * (height - o.z()) * d.z());
+        let c = o.x().powi(2) + o.y().powi(2) - k2 * (height - o.z()).powi(2);
+
+        let mut closest = None;
+        if let Some((t0, t1)) = solve_quadratic(a, b, c) {
+            for t in IntoIterator::into_iter([t0, t1]) {
+                let p = o + t * d;
+                // the equation also describes the mirrored cone above the apex
+                if t < t_min || t > t_max || !(0.0..=height).contains(&p.z()) {
+                    continue;
+                }
+
+                let rho = p.x().hypot(p.y());
+                let normal = if rho > 1e-12 {
+                    Vec3::new(p.x() / rho, p.y() / rho, slope)
+                } else {
+                    // the normal is undefined at the apex, fall back to the axis
+                    Vec3::new(0.0, 0.0, 1.0)
+                };
+                let normal = frame.to_world(normal).normalized();
+                let dpdu = frame.to_world(Vec3::new(-p.y(), p.x(), 0.0));
+                let uv = (azimuth(p), p.z() / height);
+                closest = Some(HitRecord::new(ray, t, normal, uv, dpdu));
+                t_max = t;
+                break;
+            }
+        }
+
+        if self.capped {
+            let flipped = Frame::new(self.base, self.base - self.apex);
+            if let Some(record) = hit_annulus(&flipped, ray, t_min, t_max, 0.0, 0.0, self.radius) {
+                closest = Some(record);
+            }
+        }
+
+        closest
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        let r = self.radius;
+        Some(self.frame().bounding_box(-r, r, 0.0, self.height()))
+    }
+}
+
+impl Sampleable for Cone {
+    fn area(&self) -> f64 {
+        if self.capped {
+            self.side_area() + PI * self.radius.powi(2)
+        } else {
+            self.side_area()
+        }
+    }
+
+    fn sample_surface<R: Rng>(&self, rng: &mut R) -> SurfaceSample {
+        let frame = self.frame();
+        let (height, r) = (self.height(), self.radius);
+
+        if rng.gen::<f64>() * self.area() >= self.side_area() {
+            let flipped = Frame::new(self.base, -frame.onb.w());
+            return sample_annulus(rng, &flipped, 0.0, 0.0, r);
+        }
+
+        // the circumference grows linearly towards the base, so does the density of the height
+        let s = rng.gen::<f64>().sqrt();
+        let phi = 2.0 * PI * rng.gen::<f64>();
+        let point = Vec3::new(s * r * phi.cos(), s * r * phi.sin(), height * (1.0 - s));
+        let normal = Vec3::new(phi.cos(), phi.sin(), r / height).normalized();
+
+        SurfaceSample {
+            point: frame.to_world_point(point),
+            normal: frame.to_world(normal),
+        }
+    }
+}
+
+macro_rules! into_hittable_object {
+    ($($shape:ty),*) => {
+        $(
+            impl From<$shape> for HittableObject {
+                fn from(shape: $shape) -> Self {
+                    Self::Object(Box::new(shape))
+                }
+            }
+        )*
+    };
+}
+
+into_hittable_object!(Disk, Annulus, Cylinder, Cone);
+
+/// The local frame of a shape, the z axis is the axis of the shape.
+pub(crate) struct Frame {
+    origin: Vec3,
+    pub(crate) onb: Onb,
+}
+
+impl Frame {
+    pub(crate) fn new(origin: Vec3, axis: Vec3) -> Self {
+        Self {
+            origin,
+            onb: Onb::from_w(axis),
+        }
+    }
+
+    /// The origin and the direction of the ray in local coordinates. The frame is orthonormal, so
+    /// ray parameters are the same in both coordinates.
+    pub(crate) fn ray(&self, ray: &Ray) -> (Vec3, Vec3) {
+        (
+            self.onb.world_to_local(ray.origin() - self.origin),
+            self.onb.world_to_local(ray.direction()),
+        )
+    }
+
+    pub(crate) fn to_world(&self, direction: Vec3) -> Vec3 {
+        self.onb.local_to_world(direction)
+    }
+
+    pub(crate) fn to_world_point(&self, point: Vec3) -> Vec3 {
+        self.origin + self.onb.local_to_world(point)
+    }
+
+    /// The bounding box of the local box [min, max] in x and y and [z_min, z_max] in z.
+    pub(crate) fn bounding_box(&self, min: f64, max: f64, z_min: f64, z_max: f64) -> AABB {
+        let mut corners = vec![];
+        for &x in &[min, max] {
+            for &y in &[min, max] {
+                for &z in &[z_min, z_max] {
+                    corners.push(self.to_world_point(Vec3::new(x, y, z)));
+                }
+            }
+        }
+
+        AABB::containing(&corners)
+    }
+}
+
+/// Hit the annulus around the z axis of the frame in the plane at the given height, the outward
+/// normal is the z axis.
+fn hit_annulus(
+    frame: &Frame,
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+    z: f64,
+    inner_radius: f64,
+    outer_radius: f64,
+) -> Option<HitRecord> {
+    let (o, d) = frame.ray(ray);
+    if d.z().abs() < 1e-12 {
+        // the ray is parallel to the plane
+        return None;
+    }
+
+    let t = (z - o.z()) / d.z();
+    if t < t_min || t > t_max {
+        return None;
+    }
+
+    let p = o + t * d;
+    let rho = p.x().hypot(p.y());
+    if rho < inner_radius || rho > outer_radius {
+        return None;
+    }
+
+    let v = if outer_radius > inner_radius {
+        (outer_radius - rho) / (outer_radius - inner_radius)
+    } else {
+        0.0
+    };
+    let dpdu = frame.to_world(Vec3::new(-p.y(), p.x(), 0.0));
+
+    Some(HitRecord::new(ray, t, frame.onb.w(), (azimuth(p), v), dpdu))
+}
+
+/// A point uniformly distributed on the annulus hit by [hit_annulus](hit_annulus).
+fn sample_annulus<R: Rng>(
+    rng: &mut R,
+    frame: &Frame,
+    z: f64,
+    inner_radius: f64,
+    outer_radius: f64,
+) -> SurfaceSample {
+    let (r0, r1) = (inner_radius.powi(2), outer_radius.powi(2));
+    let rho = (r0 + rng.gen::<f64>() * (r1 - r0)).sqrt();
+    let phi = 2.0 * PI * rng.gen::<f64>();
+
+    SurfaceSample {
+        point: frame.to_world_point(Vec3::new(rho * phi.cos(), rho * phi.sin(), z)),
+        normal: frame.onb.w(),
+    }
+}
+
+/// The angle of a local point around the z axis starting from +x, scaled to [0, 1).
+pub(crate) fn azimuth(p: Vec3) -> f64 {
+    let phi = p.y().atan2(p.x());
+    let phi = if phi < 0.0 { phi + 2.0 * PI } else { phi };
+
+    phi / (2.0 * PI)
+}
+
+/// The real roots of a x^2 + b x + c = 0 in ascending order, avoiding the cancellation of the
+/// textbook formula.
+fn solve_quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    if a.abs() < 1e-12 {
+        if b.abs() < 1e-12 {
+            return None;
+        }
+        let t = -c / b;
+        return Some((t, t));
+    }
+
+    let discriminant = b.powi(2) - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let q = -0.5 * (b + discriminant.sqrt().copysign(b));
+    if q == 0.0 {
+        // both b and c vanish
+        return Some((0.0, 0.0));
+    }
+
+    let (t0, t1) = (q / a, c / q);
+    Some((t0.min(t1), t0.max(t1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::{Pointing, Sphere};
+
+    #[test]
+    fn cylinder_hit() {
+        let cylinder = Cylinder::new(Vec3::new(0.0, -1.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 0.5);
+
+        let side = Ray::new(Vec3::new(2.0, 0.5, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        let record = cylinder.hit(&side, 0.0, f64::INFINITY).unwrap();
+        assert!((record.t - 1.5).abs() < 1e-9);
+        assert!((record.normal - Vec3::new(1.0, 0.0, 0.0)).norm() < 1e-9);
+        assert!((record.v - 0.75).abs() < 1e-9);
+        assert_eq!(record.pointing, Pointing::Outward);
+
+        // an open cylinder is seen from the inside through its ends
+        let along = Ray::new(Vec3::new(0.1, 3.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(cylinder.hit(&along, 0.0, f64::INFINITY).is_none());
+        let slanted = Ray::new(Vec3::new(0.0, 3.0, 0.0), Vec3::new(0.2, -1.0, 0.0));
+        let record = cylinder.hit(&slanted, 0.0, f64::INFINITY).unwrap();
+        assert_eq!(record.pointing, Pointing::Inward);
+
+        let capped = cylinder.capped();
+        let record = capped.hit(&along, 0.0, f64::INFINITY).unwrap();
+        assert!((record.t - 2.0).abs() < 1e-9);
+        assert!((record.normal - Vec3::new(0.0, 1.0, 0.0)).norm() < 1e-9);
+        assert_eq!(record.pointing, Pointing::Outward);
+    }
+
+    #[test]
+    fn cone_hit() {
+        let cone = Cone::new(Vec3::origin(), Vec3::new(0.0, 0.0, 1.0), 1.0);
+
+        // the slope is 45°, halfway up the radius is 0.5
+        let ray = Ray::new(Vec3::new(2.0, 0.0, 0.5), Vec3::new(-1.0, 0.0, 0.0));
+        let record = cone.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!((record.t - 1.5).abs() < 1e-9);
+        let expected = Vec3::new(1.0, 0.0, 1.0).normalized();
+        assert!((record.normal - expected).norm() < 1e-9);
+        assert!((record.v - 0.5).abs() < 1e-9);
+
+        // the mirrored cone above the apex isn't part of the cone
+        let above = Ray::new(Vec3::new(2.0, 0.0, 1.5), Vec3::new(-1.0, 0.0, 0.0));
+        assert!(cone.hit(&above, 0.0, f64::INFINITY).is_none());
+
+        let below = Ray::new(Vec3::new(0.2, 0.0, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        let record = cone.hit(&below, 0.0, f64::INFINITY).unwrap();
+        assert_eq!(record.pointing, Pointing::Inward);
+        let record = cone.capped().hit(&below, 0.0, f64::INFINITY).unwrap();
+        assert!((record.t - 1.0).abs() < 1e-9);
+        assert_eq!(record.pointing, Pointing::Outward);
+    }
+
+    #[test]
+    fn annulus_hit() {
+        let annulus = Annulus::new(Vec3::origin(), Vec3::new(0.0, 0.0, 1.0), 0.5, 1.0);
+        let ray = |x: f64| Ray::new(Vec3::new(x, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0));
+
+        assert!(annulus.hit(&ray(0.25), 0.0, f64::INFINITY).is_none());
+        assert!(annulus.hit(&ray(1.25), 0.0, f64::INFINITY).is_none());
+        let record = annulus.hit(&ray(0.75), 0.0, f64::INFINITY).unwrap();
+        assert!((record.v - 0.5).abs() < 1e-9);
+        assert_eq!(record.pointing, Pointing::Outward);
+
+        let disk = Disk::new(Vec3::origin(), Vec3::new(0.0, 0.0, 1.0), 1.0);
+        let record = disk.hit(&ray(0.0), 0.0, f64::INFINITY).unwrap();
+        assert!((record.v - 1.0).abs() < 1e-9);
+    }
+
+    /// Sampled points must lie on the surface with the outward normal, and rays hitting the shape
+    /// must hit its bounding box.
+    fn assert_sampleable<S: Hittable + Sampleable>(shape: &S) {
+        let mut rng = rand::thread_rng();
+        let aabb = shape.bounding_box().unwrap();
+
+        for _ in 0..1000 {
+            let sample = shape.sample_surface(&mut rng);
+            let ray = Ray::new(sample.point + 1e-3 * sample.normal, -sample.normal);
+            let record = shape.hit(&ray, 0.0, f64::INFINITY).unwrap();
+            assert!((record.hit_at - sample.point).norm() < 1e-6);
+            assert_eq!(record.pointing, Pointing::Outward);
+
+            let origin = 5.0 * Sphere::unit().random_point_on_surface(&mut rng);
+            let ray = Ray::new(origin, sample.point - origin);
+            if shape.hit(&ray, 0.0, f64::INFINITY).is_some() {
+                assert!(aabb.hit(&ray, 0.0, f64::INFINITY));
+            }
+        }
+    }
+
+    #[test]
+    fn quadrics_sampleable() {
+        let (base, top) = (Vec3::new(0.3, -0.2, 0.1), Vec3::new(-0.4, 0.8, 0.5));
+        let normal = top - base;
+
+        assert_sampleable(&Disk::new(base, normal, 0.7));
+        assert_sampleable(&Annulus::new(base, normal, 0.3, 0.7));
+        assert_sampleable(&Cylinder::new(base, top, 0.6).capped());
+        assert_sampleable(&Cone::new(base, top, 0.6).capped());
+
+        let cylinder = Cylinder::new(base, top, 0.6).capped();
+        let expected = 2.0 * PI * 0.6 * normal.norm() + 2.0 * PI * 0.36;
+        assert!((cylinder.area() - expected).abs() < 1e-9);
+    }
+}
@@ -5,12 +5,31 @@ use crate::Vec3;
 pub struct Ray {
     origin: Vec3,
     direction: Vec3,
+    wavelength: Option<f64>,
 }
 
 impl Ray {
     /// Construct a ray from an origin point and a direction.
     pub fn new(origin: Vec3, direction: Vec3) -> Self {
-        Self { origin, direction }
+        Self {
+            origin,
+            direction,
+            wavelength: None,
+        }
+    }
+
+    /// Restrict the light carried by the ray to a single wavelength in nanometers.
+    pub fn with_wavelength(self, wavelength: f64) -> Self {
+        Self {
+            wavelength: Some(wavelength),
+            ..self
+        }
+    }
+
+    /// The single wavelength in nanometers the ray carries, `None` if the ray carries light of
+    /// all wavelengths.
+    pub fn wavelength(&self) -> Option<f64> {
+        self.wavelength
     }
 
     /// Get the ray's origin.
@@ -0,0 +1,257 @@
+use std::f64::consts::PI;
+
+use rand::Rng;
+
+use crate::Vec3;
+
+/// An orthonormal basis, used to transform directions sampled in a local space where the `w` axis
+/// is the surface normal back to the world space.
+#[derive(Debug, Clone, Copy)]
+pub struct Onb {
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+impl Onb {
+    /// Build an orthonormal basis whose `w` axis points to the given direction.
+    ///
+    /// The other two axes are chosen by the branchless construction from "Building an
+    /// Orthonormal Basis, Revisited" (Duff et al. 2017), which is continuous everywhere except
+    /// across the plane z = 0.
+    pub fn from_w(w: Vec3) -> Self {
+        let w = w.normalized();
+        let sign = 1f64.copysign(w.z());
+        let a = -1.0 / (sign + w.z());
+        let b = w.x() * w.y() * a;
+
+        let u = Vec3::new(1.0 + sign * w.x() * w.x() * a, sign * b, -sign * w.x());
+        let v = Vec3::new(b, sign + w.y() * w.y() * a, -w.y());
+
+        Self { u, v, w }
+    }
+
+    /// The first tangent axis.
+    pub fn u(&self) -> Vec3 {
+        self.u
+    }
+
+    /// The second tangent axis.
+    pub fn v(&self) -> Vec3 {
+        self.v
+    }
+
+    /// The axis the basis was built around.
+    pub fn w(&self) -> Vec3 {
+        self.w
+    }
+
+    /// Transform a vector in local coordinates to the world space.
+    pub fn local_to_world(&self, a: Vec3) -> Vec3 {
+        a.x() * self.u + a.y() * self.v + a.z() * self.w
+    }
+
+    /// Transform a vector in the world space to local coordinates.
+    pub fn world_to_local(&self, a: Vec3) -> Vec3 {
+        Vec3::new(a.dot(self.u), a.dot(self.v), a.dot(self.w))
+    }
+}
+
+/// A random unit vector on the hemisphere around the +z axis, distributed proportional to the
+/// cosine of its angle to the +z axis.
+pub fn random_cosine_direction<R: Rng>(rng: &mut R) -> Vec3 {
+    let r1: f64 = rng.gen();
+    let r2: f64 = rng.gen();
+
+    let phi = 2.0 * PI * r1;
+    let r = r2.sqrt();
+
+    Vec3::new(r * phi.cos(), r * phi.sin(), (1.0 - r2).sqrt())
+}
+
+/// The PDF with respect to solid angle of [random_cosine_direction](random_cosine_direction),
+/// given the cosine of the angle between the direction and the +z axis.
+pub fn cosine_pdf(cos_theta: f64) -> f64 {
+    cos_theta.max(0.0) / PI
+}
+
+/// A random unit vector uniformly distributed in the cone around the +z axis, given the cosine of
+/// the half angle of the cone.
+pub fn random_cone_direction<R: Rng>(rng: &mut R, cos_theta_max: f64) -> Vec3 {
+    let cos_theta = 1.0 - rng.gen::<f64>() * (1.0 - cos_theta_max);
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * rng.gen::<f64>();
+
+    Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+/// A piecewise constant distribution over [0, 1), each piece has the same width.
+pub struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>,
+    integral: f64,
+}
+
+impl Distribution1D {
+    /// Build a distribution proportional to the given non-negative values of the pieces. The
+    /// distribution is uniform if all the values are zero.
+    pub fn new(func: Vec<f64>) -> Self {
+        assert!(!func.is_empty());
+
+        let n = func.len() as f64;
+        let mut cdf = Vec::with_capacity(func.len() + 1);
+        cdf.push(0.0);
+        for &f in &func {
+            cdf.push(cdf.last().unwrap() + f.abs() / n);
+        }
+
+        let integral = *cdf.last().unwrap();
+        if integral > 0.0 {
+            cdf.iter_mut().for_each(|c| *c /= integral);
+        } else {
+            cdf.iter_mut()
+                .enumerate()
+                .for_each(|(i, c)| *c = i as f64 / n);
+        }
+
+        Self {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    /// The number of pieces.
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    /// The integral of the piecewise constant function over [0, 1).
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    /// Map an uniformly distributed number in [0, 1) to the distribution, return the sampled
+    /// point, its PDF and the index of the piece it falls into.
+    pub fn sample(&self, u: f64) -> (f64, f64, usize) {
+        let offset = self
+            .cdf
+            .partition_point(|&c| c <= u)
+            .saturating_sub(1)
+            .min(self.count() - 1);
+
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        let du = if width > 0.0 {
+            (u - self.cdf[offset]) / width
+        } else {
+            0.0
+        };
+
+        let x = (offset as f64 + du) / self.count() as f64;
+        (x, self.piece_pdf(offset), offset)
+    }
+
+    /// The PDF of sampling the given point in [0, 1).
+    pub fn pdf(&self, x: f64) -> f64 {
+        let offset = ((x * self.count() as f64) as usize).min(self.count() - 1);
+        self.piece_pdf(offset)
+    }
+
+    fn piece_pdf(&self, offset: usize) -> f64 {
+        if self.integral > 0.0 {
+            self.func[offset] / self.integral
+        } else {
+            1.0
+        }
+    }
+}
+
+/// A piecewise constant distribution over [0, 1) x [0, 1) defined on a grid, sampled by first
+/// choosing a row from the marginal distribution then a column from the conditional distribution
+/// of the row.
+pub struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    /// Build a distribution proportional to the given non-negative values in row-major order.
+    pub fn new(func: &[f64], width: usize, height: usize) -> Self {
+        assert_eq!(func.len(), width * height);
+
+        let conditional: Vec<_> = func
+            .chunks(width)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(conditional.iter().map(|d| d.integral()).collect());
+
+        Self {
+            conditional,
+            marginal,
+        }
+    }
+
+    /// Map two uniformly distributed numbers in [0, 1) to the distribution, return the sampled
+    /// point (column, row) and its PDF.
+    pub fn sample(&self, u0: f64, u1: f64) -> ((f64, f64), f64) {
+        let (v, pdf_v, row) = self.marginal.sample(u1);
+        let (u, pdf_u, _) = self.conditional[row].sample(u0);
+        ((u, v), pdf_u * pdf_v)
+    }
+
+    /// The PDF of sampling the given point (column, row).
+    pub fn pdf(&self, u: f64, v: f64) -> f64 {
+        let row = ((v * self.marginal.count() as f64) as usize).min(self.marginal.count() - 1);
+        self.conditional[row].pdf(u) * self.marginal.pdf(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Sphere;
+
+    #[test]
+    fn onb_orthonormal() {
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..100 {
+            let normal = Sphere::unit().random_point_on_surface(&mut rng);
+            let onb = Onb::from_w(normal);
+
+            for axis in [onb.u(), onb.v(), onb.w()].iter() {
+                assert!((axis.norm() - 1.0).abs() < 1e-9);
+            }
+
+            assert!(onb.u().dot(onb.v()).abs() < 1e-9);
+            assert!(onb.v().dot(onb.w()).abs() < 1e-9);
+            assert!(onb.w().dot(onb.u()).abs() < 1e-9);
+
+            let a = Sphere::unit().random_point_in_sphere(&mut rng);
+            let round_trip = onb.local_to_world(onb.world_to_local(a));
+            assert!((round_trip - a).norm() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn distribution_2d_pdf() {
+        let mut rng = rand::thread_rng();
+        let func = [0.0, 1.0, 2.0, 3.0, 0.0, 0.0, 4.0, 0.5, 1.5];
+        let distribution = Distribution2D::new(&func, 3, 3);
+
+        for _ in 0..100 {
+            let ((u, v), pdf) = distribution.sample(rng.gen(), rng.gen());
+            assert!((0.0..1.0).contains(&u) && (0.0..1.0).contains(&v));
+            assert!((distribution.pdf(u, v) - pdf).abs() < 1e-9);
+            // pieces with zero value are never sampled
+            assert!(pdf > 0.0);
+        }
+
+        // the PDF integrates to 1 over the unit square
+        let integral: f64 = (0..3)
+            .flat_map(|i| (0..3).map(move |j| (i, j)))
+            .map(|(i, j)| distribution.pdf((i as f64 + 0.5) / 3.0, (j as f64 + 0.5) / 3.0) / 9.0)
+            .sum();
+        assert!((integral - 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,423 @@
+use crate::{
+    hittable::{HitRecord, Hittable, HittableObject, AABB},
+    ray::Ray,
+    Vec3,
+};
+
+/// A signed distance function, negative inside of the shape and positive outside. The distance
+/// may be underestimated, overestimated distances let sphere tracing step over thin parts of the
+/// shape.
+pub trait DistanceFn: Fn(Vec3) -> f64 + Send + Sync {}
+
+impl<F: Fn(Vec3) -> f64 + Send + Sync> DistanceFn for F {}
+
+/// A shape without closed-form intersection described by its signed distance function, e.g. a
+/// fractal. Rays are sphere traced within a bounding box, normals are the gradient of the distance
+/// function by finite differences. Surface coordinates are always zero.
+pub struct Sdf {
+    distance: Box<dyn DistanceFn>,
+    bounds: AABB,
+    precision: f64,
+}
+
+impl Sdf {
+    /// Construct a shape from its signed distance function, the shape must lie within the bounds.
+    pub fn new<F: DistanceFn + 'static>(distance: F, bounds: AABB) -> Self {
+        Self {
+            distance: Box::new(distance),
+            bounds,
+            precision: 1e-7,
+        }
+    }
+
+    /// Replace the distance to the surface at which rays are considered to hit it. Coarse
+    /// precisions speed up fractals with slowly converging distances at the cost of accuracy.
+    ///
+    /// # Default:
+    /// 1e-7
+    pub fn with_precision(mut self, precision: f64) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Locate the surface crossed between the ray parameters, the ray is on the given side of the
+    /// surface at the lower ray parameter.
+    fn bisect(&self, ray: &Ray, mut lo: f64, mut hi: f64, outside: bool) -> Option<HitRecord> {
+        const MAX_ITERATIONS: usize = 64;
+
+        for _ in 0..MAX_ITERATIONS {
+            let mid = 0.5 * (lo + hi);
+            let distance = (self.distance)(ray.at(mid));
+            if distance.abs() < self.precision {
+                return self.record(ray, mid);
+            }
+
+            if (distance >= 0.0) == outside {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+
+        self.record(ray, hi)
+    }
+
+    /// The ray parameter just past the surface hit at the given ray parameter, out of the band
+    /// within the precision around the surface, so tracing from there finds the next crossing.
+    fn leave_surface(&self, ray: &Ray, mut t: f64) -> f64 {
+        const MAX_STEPS: usize = 1000;
+
+        let speed = ray.direction().norm();
+        let mut distance = 0.0f64;
+        for _ in 0..MAX_STEPS {
+            t += distance.abs().max(self.precision) / speed;
+            distance = (self.distance)(ray.at(t));
+            if distance.abs() >= self.precision {
+                break;
+            }
+        }
+
+        t
+    }
+
+    fn record(&self, ray: &Ray, t: f64) -> Option<HitRecord> {
+        let gradient = self.gradient(ray.at(t));
+        if gradient.norm_squared() == 0.0 {
+            return None;
+        }
+
+        // the tangent is arbitrary without surface coordinates
+        Some(HitRecord::new(
+            ray,
+            t,
+            gradient.normalized(),
+            (0.0, 0.0),
+            Vec3::origin(),
+        ))
+    }
+
+    /// The gradient of the distance function by central differences. Steps larger than the
+    /// precision smooth out the noise of distance estimates close to the surface.
+    fn gradient(&self, p: Vec3) -> Vec3 {
+        let h = 10.0 * self.precision;
+        let axis = |x: f64, y: f64, z: f64| {
+            let step = Vec3::new(x, y, z) * h;
+            (self.distance)(p + step) - (self.distance)(p - step)
+        };
+
+        Vec3::new(
+            axis(1.0, 0.0, 0.0),
+            axis(0.0, 1.0, 0.0),
+            axis(0.0, 0.0, 1.0),
+        )
+    }
+}
+
+impl Hittable for Sdf {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        const MAX_STEPS: usize = 1000;
+
+        let (t_min, t_max) = self.bounds.interval(ray, t_min, t_max)?;
+        let speed = ray.direction().norm();
+
+        // the side of the surface the ray starts on
+        let outside = (self.distance)(ray.at(t_min)) >= 0.0;
+
+        let (mut previous, mut t) = (t_min, t_min);
+        for _ in 0..MAX_STEPS {
+            let distance = (self.distance)(ray.at(t));
+
+            if distance.abs() < self.precision {
+                return self.record(ray, t);
+            }
+            if (distance >= 0.0) != outside {
+                // distance estimates of fractals may overshoot, the surface was crossed in the last
+                // step and is located by bisection
+                return self.bisect(ray, previous, t, outside);
+            }
+
+            // no point of the surface is closer than the distance, so the ray can safely advance
+            previous = t;
+            t += distance.abs() / speed;
+            if t > t_max {
+                return None;
+            }
+        }
+
+        None
+    }
+
+    fn intersections(&self, ray: &Ray, t_min: f64, t_max: f64) -> Vec<HitRecord> {
+        let mut records = vec![];
+        let mut t_min = t_min;
+
+        // rays stay within the precision of the surface for a while after hitting it, each hit
+        // must be left behind before tracing to the next one
+        while let Some(record) = self.hit(ray, t_min, t_max) {
+            t_min = self.leave_surface(ray, record.t);
+            records.push(record);
+        }
+
+        records
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        Some(self.bounds.clone())
+    }
+}
+
+impl From<Sdf> for HittableObject {
+    fn from(sdf: Sdf) -> Self {
+        Self::Object(Box::new(sdf))
+    }
+}
+
+/// A sphere around the center.
+pub fn sphere(center: Vec3, radius: f64) -> impl DistanceFn {
+    move |p: Vec3| (p - center).norm() - radius
+}
+
+/// An axis-aligned box around the center with the given half extents along each axis and rounded
+/// edges of the given radius, the rounding is within the extents.
+pub fn rounded_box(center: Vec3, half_extents: Vec3, radius: f64) -> impl DistanceFn {
+    move |p: Vec3| {
+        let p = p - center;
+        let q = Vec3::new(
+            p.x().abs() - half_extents.x() + radius,
+            p.y().abs() - half_extents.y() + radius,
+            p.z().abs() - half_extents.z() + radius,
+        );
+        let outside = Vec3::new(q.x().max(0.0), q.y().max(0.0), q.z().max(0.0)).norm();
+        let inside = q.x().max(q.y()).max(q.z()).min(0.0);
+
+        outside + inside - radius
+    }
+}
+
+/// A torus around the y axis through the center.
+pub fn torus(center: Vec3, major_radius: f64, minor_radius: f64) -> impl DistanceFn {
+    move |p: Vec3| {
+        let p = p - center;
+        let ring = p.x().hypot(p.z()) - major_radius;
+        ring.hypot(p.y()) - minor_radius
+    }
+}
+
+/// The Mandelbulb fractal of the given power around the origin, within the sphere of radius 1.2
+/// for the classic power 8. More iterations add finer detail.
+pub fn mandelbulb(power: f64, iterations: usize) -> impl DistanceFn {
+    const BAILOUT: f64 = 2.0;
+
+    move |p: Vec3| {
+        let mut z = p;
+        let mut dr = 1.0;
+        let mut r = z.norm();
+
+        for _ in 0..iterations {
+            if r > BAILOUT || r == 0.0 {
+                break;
+            }
+
+            // raise z to the power in spherical coordinates and track the running derivative
+            let theta = (z.z() / r).acos() * power;
+            let phi = z.y().atan2(z.x()) * power;
+            dr = r.powf(power - 1.0) * power * dr + 1.0;
+
+            z = r.powf(power)
+                * Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                )
+                + p;
+            r = z.norm();
+        }
+
+        if r == 0.0 {
+            0.0
+        } else {
+            0.5 * r.ln() * r / dr
+        }
+    }
+}
+
+/// The shape moved by the offset.
+pub fn translate<F: DistanceFn>(shape: F, offset: Vec3) -> impl DistanceFn {
+    move |p: Vec3| shape(p - offset)
+}
+
+/// The shape scaled uniformly about the origin.
+pub fn scale<F: DistanceFn>(shape: F, factor: f64) -> impl DistanceFn {
+    move |p: Vec3| shape(p / factor) * factor
+}
+
+/// The shape inflated by the radius, rounding its edges.
+pub fn round<F: DistanceFn>(shape: F, radius: f64) -> impl DistanceFn {
+    move |p: Vec3| shape(p) - radius
+}
+
+/// The volume inside of either shape.
+pub fn union<A: DistanceFn, B: DistanceFn>(a: A, b: B) -> impl DistanceFn {
+    move |p: Vec3| a(p).min(b(p))
+}
+
+/// The volume inside of both shapes.
+pub fn intersection<A: DistanceFn, B: DistanceFn>(a: A, b: B) -> impl DistanceFn {
+    move |p: Vec3| a(p).max(b(p))
+}
+
+/// The volume inside of the first shape but outside of the second one.
+pub fn difference<A: DistanceFn, B: DistanceFn>(a: A, b: B) -> impl DistanceFn {
+    move |p: Vec3| a(p).max(-b(p))
+}
+
+/// The union of both shapes blended within the given distance of where they meet, e.g. drops
+/// merging.
+pub fn smooth_union<A: DistanceFn, B: DistanceFn>(a: A, b: B, blend: f64) -> impl DistanceFn {
+    move |p: Vec3| {
+        let (da, db) = (a(p), b(p));
+        let h = (0.5 + 0.5 * (db - da) / blend).clamp(0.0, 1.0);
+        db + (da - db) * h - blend * h * (1.0 - h)
+    }
+}
+
+/// The difference of both shapes with the edges of the carved out volume blended within the given
+/// distance.
+pub fn smooth_difference<A: DistanceFn, B: DistanceFn>(a: A, b: B, blend: f64) -> impl DistanceFn {
+    move |p: Vec3| {
+        let (da, db) = (a(p), b(p));
+        let h = (0.5 - 0.5 * (da + db) / blend).clamp(0.0, 1.0);
+        da + (-db - da) * h + blend * h * (1.0 - h)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        csg::Csg,
+        hittable::{Pointing, Sphere},
+    };
+
+    fn cube(half: f64) -> AABB {
+        AABB::new(Vec3::new(-half, -half, -half), Vec3::new(half, half, half))
+    }
+
+    #[test]
+    fn sdf_sphere_matches_sphere() {
+        let mut rng = rand::thread_rng();
+        let sdf = Sdf::new(sphere(Vec3::origin(), 1.0), cube(1.1));
+
+        for _ in 0..100 {
+            let origin = 3.0 * Sphere::unit().random_point_on_surface(&mut rng);
+            let ray = Ray::new(
+                origin,
+                Sphere::unit().random_point_in_sphere(&mut rng) - origin,
+            );
+            let record = sdf.hit(&ray, 0.0, f64::INFINITY).unwrap();
+
+            // grazing rays stop short of the exact hit, but always within the precision
+            assert!((record.hit_at.norm() - 1.0).abs() < 1e-6);
+            assert!((record.normal - record.hit_at.normalized()).norm() < 1e-4);
+            assert_eq!(record.pointing, Pointing::Outward);
+        }
+
+        // from the inside
+        let ray = Ray::new(Vec3::origin(), Vec3::new(0.0, 2.0, 0.0));
+        let record = sdf.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!((record.t - 0.5).abs() < 1e-6);
+        assert_eq!(record.pointing, Pointing::Inward);
+
+        let miss = Ray::new(Vec3::new(0.0, 1.05, -3.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(sdf.hit(&miss, 0.0, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn sdf_intersections() {
+        let ray = Ray::new(Vec3::new(-3.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let hits = |records: Vec<HitRecord>| {
+            records
+                .iter()
+                .map(|record| (record.t - 3.0, record.pointing))
+                .collect::<Vec<_>>()
+        };
+        let assert_hits = |actual: Vec<(f64, Pointing)>, expected: &[(f64, Pointing)]| {
+            assert_eq!(actual.len(), expected.len(), "{:?}", actual);
+            for ((t, pointing), (expected_t, expected_pointing)) in actual.iter().zip(expected) {
+                assert!((t - expected_t).abs() < 1e-6, "{} {}", t, expected_t);
+                assert_eq!(pointing, expected_pointing);
+            }
+        };
+
+        let sdf = Sdf::new(sphere(Vec3::origin(), 1.0), cube(1.1));
+        assert_hits(
+            hits(sdf.intersections(&ray, 0.0, f64::INFINITY)),
+            &[(-1.0, Pointing::Outward), (1.0, Pointing::Inward)],
+        );
+
+        // traced shapes can be combined with other closed objects
+        let sdf_sphere = |x: f64| {
+            let center = Vec3::new(x, 0.0, 0.0);
+            let bounds = AABB::new(
+                center - Vec3::new(1.1, 1.1, 1.1),
+                center + Vec3::new(1.1, 1.1, 1.1),
+            );
+            Sdf::new(sphere(center, 1.0), bounds)
+        };
+        let union = Csg::union(sdf_sphere(-0.5), sdf_sphere(0.5));
+        assert_hits(
+            hits(union.intersections(&ray, 0.0, f64::INFINITY)),
+            &[(-1.5, Pointing::Outward), (1.5, Pointing::Inward)],
+        );
+        let lens = Csg::intersection(sdf_sphere(-0.5), sdf_sphere(0.5));
+        assert_hits(
+            hits(lens.intersections(&ray, 0.0, f64::INFINITY)),
+            &[(-0.5, Pointing::Outward), (0.5, Pointing::Inward)],
+        );
+    }
+
+    #[test]
+    fn sdf_combinators() {
+        let p = Vec3::new(0.0, 0.0, 0.0);
+        let half = Vec3::new(1.0, 0.5, 0.5);
+
+        let shape = rounded_box(Vec3::origin(), half, 0.1);
+        assert!((shape(Vec3::new(2.0, 0.0, 0.0)) - 1.0).abs() < 1e-9);
+        // the corner is rounded off
+        let corner = shape(half);
+        assert!((corner - (0.1 * 3f64.sqrt() - 0.1)).abs() < 1e-9);
+
+        let a = sphere(Vec3::new(-0.6, 0.0, 0.0), 0.5);
+        let b = sphere(Vec3::new(0.6, 0.0, 0.0), 0.5);
+        assert!((union(&a, &b)(p) - 0.1).abs() < 1e-9);
+        // blending fills the gap between the spheres
+        assert!(smooth_union(&a, &b, 0.5)(p) < 0.0);
+        assert!(smooth_union(&a, &b, 0.5)(Vec3::new(2.0, 0.0, 0.0)) > 0.0);
+
+        let bitten = difference(sphere(p, 1.0), sphere(Vec3::new(1.0, 0.0, 0.0), 0.5));
+        assert!(bitten(Vec3::new(0.9, 0.0, 0.0)) > 0.0);
+        assert!(bitten(Vec3::new(-0.9, 0.0, 0.0)) < 0.0);
+        assert!(intersection(&a, &b)(p) > 0.0);
+        let carved = smooth_difference(sphere(p, 1.0), sphere(Vec3::new(1.0, 0.0, 0.0), 0.5), 0.2);
+        assert!(carved(Vec3::new(0.9, 0.0, 0.0)) > 0.0);
+        assert!(carved(Vec3::new(-0.9, 0.0, 0.0)) < 0.0);
+
+        let moved = scale(translate(sphere(p, 1.0), Vec3::new(1.0, 0.0, 0.0)), 2.0);
+        assert!((moved(Vec3::new(5.0, 0.0, 0.0)) - 1.0).abs() < 1e-9);
+        assert!((round(torus(p, 2.0, 0.5), 0.1)(Vec3::new(2.0, 1.0, 0.0)) - 0.4).abs() < 1e-9);
+    }
+
+    #[test]
+    fn mandelbulb_hit() {
+        let sdf = Sdf::new(mandelbulb(8.0, 10), cube(1.3)).with_precision(1e-5);
+
+        let ray = Ray::new(Vec3::new(0.1, 0.2, -3.0), Vec3::new(0.0, 0.0, 1.0));
+        let record = sdf.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!(record.hit_at.norm() < 1.3);
+        assert!((record.normal.norm() - 1.0).abs() < 1e-9);
+        assert_eq!(record.pointing, Pointing::Outward);
+
+        let miss = Ray::new(Vec3::new(1.25, 0.0, -3.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(sdf.hit(&miss, 0.0, f64::INFINITY).is_none());
+    }
+}
@@ -0,0 +1,212 @@
+use std::f64::consts::FRAC_PI_2;
+
+use crate::{color::Rgb, light::DirectionalLight, Vec3};
+
+/// Angular radius of the sun seen from the earth in degrees.
+const SUN_ANGULAR_RADIUS: f64 = 0.27;
+
+/// The analytic daylight sky model from "A Practical Analytic Model for Daylight" (Preetham et al.
+/// 1999), parameterized by the direction of the sun and the turbidity of the atmosphere.
+///
+/// The sky itself doesn't include the sun disk, the sun should be added to the world as a light
+/// source by [sun](Sky::sun).
+pub struct Sky {
+    sun_direction: Vec3,
+    turbidity: f64,
+    intensity: f64,
+    theta_sun: f64,
+    zenith: [f64; 3],
+    perez: [Perez; 3],
+}
+
+impl Sky {
+    /// Construct a sky lit by the sun in the given direction (pointing from the ground towards
+    /// the sun) with the given turbidity. Turbidity is the ratio of the optical thickness of the
+    /// atmosphere to a pure air atmosphere, 2 is a very clear day, 3 a clear day and 6 a warm
+    /// moist day.
+    pub fn new(sun_direction: Vec3, turbidity: f64) -> Self {
+        let sun_direction = sun_direction.normalized();
+        let t = turbidity;
+        // the model breaks down when the sun is under the horizon
+        let theta_sun = sun_direction
+            .y()
+            .clamp(0.0, 1.0)
+            .acos()
+            .min(FRAC_PI_2 - 1e-3);
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (std::f64::consts::PI - 2.0 * theta_sun);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+
+        let thetas = [theta_sun.powi(3), theta_sun.powi(2), theta_sun, 1.0];
+        let chromaticity = |m: [[f64; 4]; 3]| {
+            let row = |r: [f64; 4]| r.iter().zip(&thetas).map(|(a, b)| a * b).sum::<f64>();
+            t * t * row(m[0]) + t * row(m[1]) + row(m[2])
+        };
+
+        let zenith_x = chromaticity([
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let zenith_y = chromaticity([
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+
+        let perez = [
+            Perez::new([
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ]),
+            Perez::new([
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ]),
+            Perez::new([
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ]),
+        ];
+
+        Self {
+            sun_direction,
+            turbidity,
+            intensity: 0.1,
+            theta_sun,
+            zenith: [zenith_luminance, zenith_x, zenith_y],
+            perez,
+        }
+    }
+
+    /// Set the multiplier converting luminance in kcd/m^2 of the model to the radiance of the
+    /// renderer, applied to both the sky and the sun.
+    ///
+    /// # Default:
+    /// 0.1
+    pub fn intensity(&mut self, intensity: f64) -> &mut Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// The radiance arriving from the given direction. Directions under the horizon see the sky
+    /// at the horizon.
+    pub fn radiance(&self, direction: Vec3) -> Rgb {
+        let direction = direction.normalized();
+        let theta = direction.y().clamp(0.0, 1.0).acos().min(FRAC_PI_2 - 1e-3);
+        let gamma = direction.dot(self.sun_direction).clamp(-1.0, 1.0).acos();
+
+        let [luminance, x, y] = [0, 1, 2].map(|i| {
+            self.zenith[i] * self.perez[i].eval(theta, gamma)
+                / self.perez[i].eval(0.0, self.theta_sun)
+        });
+
+        self.intensity * xyy_to_rgb(x, y, luminance.max(0.0))
+    }
+
+    /// The sun matching the sky as a light source, its color is the extraterrestrial sunlight
+    /// attenuated by Rayleigh and aerosol scattering along its path through the atmosphere.
+    pub fn sun(&self) -> DirectionalLight {
+        // illuminance of the sun on a perpendicular surface outside the atmosphere in kilolux
+        const SOLAR_ILLUMINANCE: f64 = 128.0;
+        // wavelengths in micrometers representing the red, green and blue channels
+        const WAVELENGTHS: [f64; 3] = [0.68, 0.55, 0.44];
+
+        let theta_degrees = self.theta_sun.to_degrees();
+        // relative optical air mass (Kasten and Young 1989)
+        let air_mass =
+            1.0 / (self.theta_sun.cos() + 0.50572 * (96.07995 - theta_degrees).powf(-1.6364));
+
+        // Angstrom turbidity formula from the Preetham paper
+        let alpha = 1.3;
+        let beta = 0.04608 * self.turbidity - 0.04586;
+
+        let [r, g, b] = WAVELENGTHS.map(|lambda| {
+            let rayleigh = 0.008735 * lambda.powf(-4.08);
+            let aerosol = beta * lambda.powf(-alpha);
+            (-air_mass * (rayleigh + aerosol)).exp()
+        });
+
+        let irradiance = (self.intensity * SOLAR_ILLUMINANCE) * Rgb::new(r, g, b);
+        DirectionalLight::disk(-self.sun_direction, irradiance, SUN_ANGULAR_RADIUS)
+    }
+}
+
+/// The Perez sky luminance distribution function.
+struct Perez {
+    a: f64,
+    b: f64,
+    c: f64,
+    d: f64,
+    e: f64,
+}
+
+impl Perez {
+    fn new([a, b, c, d, e]: [f64; 5]) -> Self {
+        Self { a, b, c, d, e }
+    }
+
+    fn eval(&self, theta: f64, gamma: f64) -> f64 {
+        (1.0 + self.a * (self.b / theta.cos()).exp())
+            * (1.0 + self.c * (self.d * gamma).exp() + self.e * gamma.cos().powi(2))
+    }
+}
+
+/// Convert a color in CIE xyY color space to linear sRGB, colors out of the sRGB gamut are clipped.
+fn xyy_to_rgb(x: f64, y: f64, luminance: f64) -> Rgb {
+    if y <= 0.0 {
+        return Rgb::new(0.0, 0.0, 0.0);
+    }
+
+    let big_x = x / y * luminance;
+    let big_z = (1.0 - x - y) / y * luminance;
+
+    Rgb::new(
+        (3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z).max(0.0),
+        (-0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z).max(0.0),
+        (0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z).max(0.0),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light::Light;
+
+    #[test]
+    fn sky_brighter_around_sun() {
+        let sun_direction = Vec3::new(1.0, 1.0, 0.0);
+        let sky = Sky::new(sun_direction, 3.0);
+
+        let near_sun = sky.radiance(Vec3::new(1.0, 0.9, 0.1));
+        let away_from_sun = sky.radiance(Vec3::new(-1.0, 0.9, 0.0));
+        assert!(near_sun.luminance() > away_from_sun.luminance());
+
+        // clear sky is blue away from the sun
+        let zenith = sky.radiance(Vec3::new(-0.5, 1.0, 0.0));
+        assert!(zenith.b() > zenith.r());
+    }
+
+    #[test]
+    fn sunset_is_red() {
+        let sun_color = |sun_direction| {
+            let sun = Light::from(Sky::new(sun_direction, 3.0).sun());
+            let sample = sun.sample(&mut rand::thread_rng(), Vec3::origin()).unwrap();
+            sample.radiance.r() / sample.radiance.b()
+        };
+
+        let noon = sun_color(Vec3::new(0.0, 1.0, 0.2));
+        let sunset = sun_color(Vec3::new(0.0, 0.05, 1.0));
+        assert!(sunset > noon);
+        assert!(noon > 1.0);
+    }
+}
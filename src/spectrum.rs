@@ -0,0 +1,222 @@
+use std::sync::OnceLock;
+
+use rand::Rng;
+
+use crate::color::Rgb;
+
+/// The shortest visible wavelength in nanometers.
+pub const LAMBDA_MIN: f64 = 380.0;
+/// The longest visible wavelength in nanometers.
+pub const LAMBDA_MAX: f64 = 780.0;
+
+/// Sample a visible wavelength in nanometers uniformly.
+pub fn sample_wavelength<R: Rng>(rng: &mut R) -> f64 {
+    rng.gen_range(LAMBDA_MIN..LAMBDA_MAX)
+}
+
+/// The CIE 1931 standard observer color matching functions at the given wavelength in
+/// nanometers, approximated by the multi-lobe Gaussian fit from "Simple Analytic Approximations to
+/// the CIE XYZ Color Matching Functions" (Wyman et al. 2013).
+pub fn cie_xyz(lambda: f64) -> [f64; 3] {
+    let g = |mu: f64, sigma_lo: f64, sigma_hi: f64| {
+        let sigma = if lambda < mu { sigma_lo } else { sigma_hi };
+        (-0.5 * ((lambda - mu) / sigma).powi(2)).exp()
+    };
+
+    let x =
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2);
+    let y = 0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1);
+    let z = 1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8);
+
+    [x, y, z]
+}
+
+/// Convert a color in CIE XYZ color space to linear sRGB. Colors out of the sRGB gamut have
+/// negative channels.
+pub fn xyz_to_rgb([x, y, z]: [f64; 3]) -> Rgb {
+    Rgb::new(
+        3.2406 * x - 1.5372 * y - 0.4986 * z,
+        -0.9689 * x + 1.8758 * y + 0.0415 * z,
+        0.0557 * x - 0.2040 * y + 1.0570 * z,
+    )
+}
+
+/// The weight converting light of a single wavelength sampled by
+/// [sample_wavelength](sample_wavelength) back to linear sRGB. The weights are normalized so that
+/// their expected value over all wavelengths is white, i.e. a flat spectrum appears white.
+pub fn wavelength_to_rgb(lambda: f64) -> Rgb {
+    static WHITE: OnceLock<Rgb> = OnceLock::new();
+
+    let white = WHITE.get_or_init(|| {
+        // the average of the unnormalized weights over the visible spectrum at 1nm intervals
+        let n = (LAMBDA_MAX - LAMBDA_MIN) as usize;
+        let sum = (0..n)
+            .map(|i| xyz_to_rgb(cie_xyz(LAMBDA_MIN + i as f64 + 0.5)))
+            .fold(Rgb::default(), |acc, rgb| acc + rgb);
+        sum / n as f64
+    });
+
+    let rgb = xyz_to_rgb(cie_xyz(lambda));
+    Rgb::new(
+        rgb.r() / white.r(),
+        rgb.g() / white.g(),
+        rgb.b() / white.b(),
+    )
+}
+
+/// Uplift a linear sRGB color to a smooth spectrum and evaluate it at the given wavelength in
+/// nanometers.
+///
+/// The spectrum is a combination of three smooth basis functions covering the blue, green and red
+/// part of the visible spectrum. The basis functions sum to one everywhere, so gray colors are
+/// uplifted to flat spectra and reflectances in [0, 1] stay in [0, 1]. Saturated colors only
+/// approximately survive the round trip back to sRGB.
+pub fn rgb_to_spectrum(rgb: Rgb, lambda: f64) -> f64 {
+    let smooth_step = |lo: f64, hi: f64| {
+        let t = ((lambda - lo) / (hi - lo)).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    };
+
+    let blue = 1.0 - smooth_step(465.0, 505.0);
+    let red = smooth_step(570.0, 610.0);
+    let green = 1.0 - blue - red;
+
+    rgb.r() * red + rgb.g() * green + rgb.b() * blue
+}
+
+/// Index of refraction as a function of the wavelength.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ior {
+    /// The same index of refraction for all wavelengths, the material is not dispersive.
+    Constant(f64),
+    /// The Cauchy equation `n = a + b / λ^2`, with λ in micrometers.
+    Cauchy {
+        /// The constant term.
+        a: f64,
+        /// The coefficient of the inverse squared wavelength in μm^2.
+        b: f64,
+    },
+    /// The Sellmeier equation `n^2 = 1 + Σ b λ^2 / (λ^2 - c)`, with λ in micrometers.
+    Sellmeier {
+        /// The B coefficients.
+        b: [f64; 3],
+        /// The C coefficients in μm^2.
+        c: [f64; 3],
+    },
+}
+
+impl Ior {
+    /// Borosilicate crown glass (Schott N-BK7), a common optical glass.
+    pub fn bk7() -> Self {
+        Self::Sellmeier {
+            b: [1.03961212, 0.231792344, 1.01046945],
+            c: [0.00600069867, 0.0200179144, 103.560653],
+        }
+    }
+
+    /// Diamond, a highly dispersive gem.
+    pub fn diamond() -> Self {
+        Self::Sellmeier {
+            b: [0.3306, 4.3356, 0.0],
+            c: [0.030625, 0.011236, 0.0],
+        }
+    }
+
+    /// Return whether the index of refraction depends on the wavelength.
+    pub fn is_dispersive(&self) -> bool {
+        !matches!(self, Ior::Constant(_))
+    }
+
+    /// The index of refraction at the given wavelength in nanometers.
+    pub fn at(&self, lambda: f64) -> f64 {
+        let micrometers = lambda / 1000.0;
+        let l2 = micrometers * micrometers;
+
+        match *self {
+            Ior::Constant(n) => n,
+            Ior::Cauchy { a, b } => a + b / l2,
+            Ior::Sellmeier { b, c } => {
+                let n2 = 1.0
+                    + b.iter()
+                        .zip(c.iter())
+                        .map(|(b, c)| b * l2 / (l2 - c))
+                        .sum::<f64>();
+                n2.sqrt()
+            }
+        }
+    }
+
+    /// A representative index of refraction for light without a specific wavelength, evaluated
+    /// at the Fraunhofer d line (587.6nm).
+    pub fn nominal(&self) -> f64 {
+        self.at(587.6)
+    }
+}
+
+impl From<f64> for Ior {
+    fn from(n: f64) -> Self {
+        Self::Constant(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flat_spectrum_is_white() {
+        let mut rng = rand::thread_rng();
+        const N: usize = 100_000;
+
+        let mean = (0..N)
+            .map(|_| wavelength_to_rgb(sample_wavelength(&mut rng)))
+            .fold(Rgb::default(), |acc, rgb| acc + rgb)
+            / N as f64;
+
+        for channel in [mean.r(), mean.g(), mean.b()].iter() {
+            assert!((channel - 1.0).abs() < 0.05, "{}", channel);
+        }
+    }
+
+    #[test]
+    fn uplift_round_trip() {
+        let round_trip = |rgb: Rgb| {
+            let n = (LAMBDA_MAX - LAMBDA_MIN) as usize;
+            (0..n)
+                .map(|i| {
+                    let lambda = LAMBDA_MIN + i as f64 + 0.5;
+                    rgb_to_spectrum(rgb, lambda) * wavelength_to_rgb(lambda)
+                })
+                .fold(Rgb::default(), |acc, rgb| acc + rgb)
+                / n as f64
+        };
+
+        let gray = round_trip(Rgb::new(0.5, 0.5, 0.5));
+        assert!((*gray - *Rgb::new(0.5, 0.5, 0.5)).norm() < 1e-3);
+
+        for &rgb in [
+            Rgb::new(0.8, 0.2, 0.1),
+            Rgb::new(0.1, 0.7, 0.2),
+            Rgb::new(0.2, 0.3, 0.9),
+        ]
+        .iter()
+        {
+            assert!((*round_trip(rgb) - *rgb).norm() < 0.3);
+        }
+    }
+
+    #[test]
+    fn glass_dispersion() {
+        let bk7 = Ior::bk7();
+        assert!((bk7.nominal() - 1.5168).abs() < 1e-3);
+        // blue light bends more than red light
+        assert!(bk7.at(450.0) > bk7.at(650.0));
+
+        let cauchy = Ior::Cauchy {
+            a: 1.5046,
+            b: 0.00420,
+        };
+        assert!(cauchy.at(450.0) > cauchy.at(650.0));
+        assert!(!Ior::Constant(1.5).is_dispersive());
+    }
+}
@@ -0,0 +1,196 @@
+use std::path::Path;
+
+use crate::color::Rgb;
+
+/// Colors or scalar parameters varying over the surface of an object, looked up by the surface
+/// coordinates of a hit point.
+pub enum Texture {
+    /// The same value everywhere.
+    Constant(Rgb),
+    /// A checkerboard alternating between two textures.
+    Checker(Checker),
+    /// An image stretched over the surface.
+    Image(ImageTexture),
+}
+
+impl Texture {
+    /// The color of the texture at the given surface coordinates.
+    pub fn value(&self, u: f64, v: f64) -> Rgb {
+        match self {
+            Texture::Constant(rgb) => *rgb,
+            Texture::Checker(c) => c.value(u, v),
+            Texture::Image(i) => i.value(u, v),
+        }
+    }
+
+    /// The scalar parameter stored in the texture at the given surface coordinates. Textures of
+    /// scalar parameters are expected to be gray, only the red channel is read.
+    pub fn scalar(&self, u: f64, v: f64) -> f64 {
+        self.value(u, v).r()
+    }
+}
+
+impl From<Rgb> for Texture {
+    fn from(rgb: Rgb) -> Self {
+        Self::Constant(rgb)
+    }
+}
+
+impl From<f64> for Texture {
+    fn from(value: f64) -> Self {
+        Self::Constant(Rgb::new(value, value, value))
+    }
+}
+
+impl From<Checker> for Texture {
+    fn from(c: Checker) -> Self {
+        Self::Checker(c)
+    }
+}
+
+impl From<ImageTexture> for Texture {
+    fn from(i: ImageTexture) -> Self {
+        Self::Image(i)
+    }
+}
+
+/// A checkerboard alternating between two textures in surface coordinates.
+pub struct Checker {
+    odd: Box<Texture>,
+    even: Box<Texture>,
+    frequency: f64,
+}
+
+impl Checker {
+    /// Construct a checkerboard with the given number of squares along both surface coordinates.
+    pub fn new<O: Into<Texture>, E: Into<Texture>>(odd: O, even: E, frequency: f64) -> Self {
+        Self {
+            odd: Box::new(odd.into()),
+            even: Box::new(even.into()),
+            frequency,
+        }
+    }
+
+    fn value(&self, u: f64, v: f64) -> Rgb {
+        let i = (u * self.frequency).floor() as i64;
+        let j = (v * self.frequency).floor() as i64;
+
+        if (i + j).rem_euclid(2) == 1 {
+            self.odd.value(u, v)
+        } else {
+            self.even.value(u, v)
+        }
+    }
+}
+
+/// An image stretched over the surface with bilinear filtering, wrapping around at the borders.
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgb>,
+}
+
+impl ImageTexture {
+    /// Load a color texture from an image file. Pixels are decoded by the same gamma 2 images are
+    /// encoded with when rendered.
+    pub fn open<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        Self::load(path, |channel| channel * channel)
+    }
+
+    /// Load a texture from an image file without gamma decoding, for textures of scalar
+    /// parameters and normal maps.
+    pub fn open_linear<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        Self::load(path, |channel| channel)
+    }
+
+    /// Load the alpha channel of an image file as a gray texture, e.g. the opacity of a leaf.
+    pub fn open_alpha<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let image = image::open(path)?.to_rgba8();
+        let (width, height) = image.dimensions();
+
+        let pixels = image
+            .pixels()
+            .map(|pixel| {
+                let alpha = pixel.0[3] as f64 / 255.0;
+                Rgb::new(alpha, alpha, alpha)
+            })
+            .collect();
+
+        Ok(Self::from_pixels(width as usize, height as usize, pixels))
+    }
+
+    fn load<P: AsRef<Path>, F: Fn(f64) -> f64>(path: P, decode: F) -> anyhow::Result<Self> {
+        let image = image::open(path)?.to_rgb8();
+        let (width, height) = image.dimensions();
+
+        let pixels = image
+            .pixels()
+            .map(|pixel| {
+                let [r, g, b] = pixel.0.map(|channel| decode(channel as f64 / 255.0));
+                Rgb::new(r, g, b)
+            })
+            .collect();
+
+        Ok(Self::from_pixels(width as usize, height as usize, pixels))
+    }
+
+    /// Construct a texture from linear pixels in row-major order, the first row is the top of the
+    /// image at v = 1.
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<Rgb>) -> Self {
+        assert!(width > 0 && height > 0);
+        assert_eq!(pixels.len(), width * height);
+
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    fn pixel(&self, x: i64, y: i64) -> Rgb {
+        let x = x.rem_euclid(self.width as i64) as usize;
+        let y = y.rem_euclid(self.height as i64) as usize;
+        self.pixels[y * self.width + x]
+    }
+
+    fn value(&self, u: f64, v: f64) -> Rgb {
+        // pixel centers are at half-integer coordinates
+        let x = u * self.width as f64 - 0.5;
+        let y = (1.0 - v) * self.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        (1.0 - ty) * ((1.0 - tx) * self.pixel(x0, y0) + tx * self.pixel(x0 + 1, y0))
+            + ty * ((1.0 - tx) * self.pixel(x0, y0 + 1) + tx * self.pixel(x0 + 1, y0 + 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color;
+
+    #[test]
+    fn checker_alternates() {
+        let checker = Texture::from(Checker::new(color::BLACK, color::WHITE, 4.0));
+
+        assert_eq!(checker.scalar(0.1, 0.1), 1.0);
+        assert_eq!(checker.scalar(0.3, 0.1), 0.0);
+        assert_eq!(checker.scalar(0.3, 0.3), 1.0);
+    }
+
+    #[test]
+    fn image_bilinear() {
+        let pixels = vec![color::BLACK, color::WHITE];
+        let texture = Texture::from(ImageTexture::from_pixels(2, 1, pixels));
+
+        // at pixel centers
+        assert!((texture.scalar(0.25, 0.5) - 0.0).abs() < 1e-9);
+        assert!((texture.scalar(0.75, 0.5) - 1.0).abs() < 1e-9);
+        // halfway between the pixels
+        assert!((texture.scalar(0.5, 0.5) - 0.5).abs() < 1e-9);
+        // wrapping around the border
+        assert!((texture.scalar(0.0, 0.5) - 0.5).abs() < 1e-9);
+    }
+}
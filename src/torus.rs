@@ -0,0 +1,302 @@
+use std::f64::consts::PI;
+
+use crate::{
+    hittable::{HitRecord, Hittable, HittableObject, AABB},
+    quadric::{self, Frame},
+    ray::Ray,
+    Vec3,
+};
+
+/// A torus around an axis through its center, e.g. a ring or a donut. u is the angle around the
+/// axis, v is the angle around the tube starting from the outer equator.
+pub struct Torus {
+    /// Center of the torus.
+    pub center: Vec3,
+    /// The axis the tube revolves around.
+    pub axis: Vec3,
+    /// Distance from the center to the center of the tube.
+    pub major_radius: f64,
+    /// Radius of the tube.
+    pub minor_radius: f64,
+}
+
+impl Torus {
+    /// Construct a ring torus, the tube must be thinner than the distance from the center to the
+    /// tube so the torus has a hole.
+    pub fn new(center: Vec3, axis: Vec3, major_radius: f64, minor_radius: f64) -> Self {
+        assert!(0.0 < minor_radius && minor_radius < major_radius);
+
+        Self {
+            center,
+            axis,
+            major_radius,
+            minor_radius,
+        }
+    }
+
+    fn frame(&self) -> Frame {
+        Frame::new(self.center, self.axis)
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (big_r, r) = (self.major_radius, self.minor_radius);
+        let frame = self.frame();
+        let (o, d) = frame.ray(ray);
+
+        // rays are normalized and moved to the point closest to the center, so the coefficients of
+        // the quartic stay well-scaled even for distant ray origins
+        let scale = d.norm();
+        let d = d / scale;
+        let shift = -o.dot(d);
+        let o = o + shift * d;
+
+        // the ray parameter range within the bounding sphere
+        let half_chord_squared = (big_r + r).powi(2) - o.norm_squared();
+        if half_chord_squared < 0.0 {
+            return None;
+        }
+        let half_chord = half_chord_squared.sqrt();
+        let lo = (t_min * scale - shift).max(-half_chord);
+        let hi = (t_max * scale - shift).min(half_chord);
+        if lo > hi {
+            return None;
+        }
+
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + y^2) for p = o + t d
+        let n = o.dot(d);
+        let k = o.norm_squared() + big_r.powi(2) - r.powi(2);
+        let planar = |a: Vec3, b: Vec3| a.x() * b.x() + a.y() * b.y();
+        let four_r2 = 4.0 * big_r.powi(2);
+        let quartic = [
+            1.0,
+            4.0 * n,
+            4.0 * n.powi(2) + 2.0 * k - four_r2 * planar(d, d),
+            4.0 * n * k - 2.0 * four_r2 * planar(o, d),
+            k.powi(2) - four_r2 * planar(o, o),
+        ];
+
+        let t = *polynomial_roots(&quartic, lo, hi).first()?;
+        let p = o + t * d;
+
+        // the normal points away from the closest point on the circle through the tube
+        let rho = p.x().hypot(p.y());
+        let core = Vec3::new(p.x(), p.y(), 0.0) * (big_r / rho);
+        let normal = frame.to_world((p - core).normalized());
+        let dpdu = frame.to_world(Vec3::new(-p.y(), p.x(), 0.0));
+
+        let phi = p.z().atan2(rho - big_r);
+        let phi = if phi < 0.0 { phi + 2.0 * PI } else { phi };
+        let uv = (quadric::azimuth(p), phi / (2.0 * PI));
+
+        Some(HitRecord::new(ray, (t + shift) / scale, normal, uv, dpdu))
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        let (big_r, r) = (self.major_radius, self.minor_radius);
+        Some(self.frame().bounding_box(-(big_r + r), big_r + r, -r, r))
+    }
+}
+
+impl From<Torus> for HittableObject {
+    fn from(torus: Torus) -> Self {
+        Self::Object(Box::new(torus))
+    }
+}
+
+/// The polynomial with coefficients in descending order of powers evaluated at x.
+fn evaluate(coefficients: &[f64], x: f64) -> f64 {
+    coefficients.iter().fold(0.0, |sum, c| sum * x + c)
+}
+
+/// The coefficients of the derivative of the polynomial, in descending order of powers.
+fn derivative(coefficients: &[f64]) -> Vec<f64> {
+    let degree = coefficients.len() - 1;
+    coefficients[..degree]
+        .iter()
+        .enumerate()
+        .map(|(i, c)| c * (degree - i) as f64)
+        .collect()
+}
+
+/// The real roots of the polynomial within [lo, hi] in ascending order.
+///
+/// The roots of the derivative split the range into pieces where the polynomial is monotonic, each
+/// piece contains at most one root, which is found by Newton's method safeguarded by bisection.
+/// Unlike closed-form solutions, this doesn't lose the close pair of roots of grazing rays to
+/// cancellation. Roots of even multiplicity, where the polynomial touches zero without crossing
+/// it, are only found if they are hit exactly.
+pub fn polynomial_roots(coefficients: &[f64], lo: f64, hi: f64) -> Vec<f64> {
+    if coefficients.len() < 2 {
+        return vec![];
+    }
+
+    let mut bounds = vec![lo];
+    bounds.extend(polynomial_roots(&derivative(coefficients), lo, hi));
+    bounds.push(hi);
+
+    let derivative = derivative(coefficients);
+    let mut roots: Vec<f64> = vec![];
+    for piece in bounds.windows(2) {
+        let (a, b) = (piece[0], piece[1]);
+        let (fa, fb) = (evaluate(coefficients, a), evaluate(coefficients, b));
+
+        let root = if fa == 0.0 {
+            a
+        } else if fb == 0.0 {
+            b
+        } else if fa.signum() != fb.signum() {
+            bracketed_root(coefficients, &derivative, a, b, fa)
+        } else {
+            continue;
+        };
+
+        // roots at the bounds are shared by adjacent pieces
+        match roots.last() {
+            Some(&last) if root <= last => {}
+            _ => roots.push(root),
+        }
+    }
+
+    roots
+}
+
+/// The single root of the polynomial in [a, b], where f(a) = fa has the opposite sign of f(b).
+fn bracketed_root(
+    coefficients: &[f64],
+    derivative: &[f64],
+    mut a: f64,
+    mut b: f64,
+    fa: f64,
+) -> f64 {
+    const MAX_ITERATIONS: usize = 100;
+
+    let mut x = 0.5 * (a + b);
+    for _ in 0..MAX_ITERATIONS {
+        let fx = evaluate(coefficients, x);
+        if fx == 0.0 {
+            break;
+        }
+
+        // keep the root bracketed
+        if fx.signum() == fa.signum() {
+            a = x;
+        } else {
+            b = x;
+        }
+        if b - a <= 1e-14 * x.abs().max(1.0) {
+            break;
+        }
+
+        // Newton steps leaving the bracket are replaced by bisection
+        let next = x - fx / evaluate(derivative, x);
+        x = if a < next && next < b {
+            next
+        } else {
+            0.5 * (a + b)
+        };
+    }
+
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::{Pointing, Sphere};
+
+    fn ring() -> Torus {
+        Torus::new(Vec3::origin(), Vec3::new(0.0, 0.0, 1.0), 2.0, 0.5)
+    }
+
+    #[test]
+    fn polynomial_roots_found() {
+        // (x - 1)(x - 1.001)(x + 2)(x - 3), two close roots like the ones of a grazing ray
+        let coefficients = [1.0, -3.001, -2.998, 11.005, -6.006];
+        let roots = polynomial_roots(&coefficients, -10.0, 10.0);
+
+        assert_eq!(roots.len(), 4);
+        for (root, expected) in roots.iter().zip(&[-2.0, 1.0, 1.001, 3.0]) {
+            assert!((root - expected).abs() < 1e-9, "{}", root);
+        }
+        assert_eq!(polynomial_roots(&coefficients, 1.5, 2.5).len(), 0);
+    }
+
+    #[test]
+    fn torus_hit() {
+        let torus = ring();
+
+        let ray = Ray::new(Vec3::new(5.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        let record = torus.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!((record.t - 2.5).abs() < 1e-9);
+        assert!((record.normal - Vec3::new(1.0, 0.0, 0.0)).norm() < 1e-9);
+        assert!(record.u.abs() < 1e-9 && record.v.abs() < 1e-9);
+        assert_eq!(record.pointing, Pointing::Outward);
+
+        // the second hit leaves the tube from the inside
+        let record = torus.hit(&ray, 2.6, f64::INFINITY).unwrap();
+        assert!((record.t - 3.5).abs() < 1e-9);
+        assert_eq!(record.pointing, Pointing::Inward);
+
+        // through the hole
+        let hole = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(torus.hit(&hole, 0.0, f64::INFINITY).is_none());
+
+        // from far away, hits must still be accurate
+        let far = Ray::new(Vec3::new(1e5, 1e-3, 0.1), Vec3::new(-1.0, 0.0, 0.0));
+        let record = torus.hit(&far, 0.0, f64::INFINITY).unwrap();
+        let expected = 2.0 + (0.25f64 - 0.01).sqrt();
+        assert!((record.hit_at.x() - expected).abs() < 1e-6);
+    }
+
+    #[test]
+    fn torus_grazing() {
+        let torus = ring();
+        let aabb = torus.bounding_box().unwrap();
+
+        // rays just below and above the top of the tube
+        for &(z, hits) in &[(0.5 - 1e-7, true), (0.5 + 1e-7, false)] {
+            let ray = Ray::new(Vec3::new(-5.0, 2.0, z), Vec3::new(1.0, 0.0, 0.0));
+            assert_eq!(torus.hit(&ray, 0.0, f64::INFINITY).is_some(), hits);
+        }
+
+        // rays just inside and outside of the outer equator
+        for &(y, hits) in &[(2.5 - 1e-7, true), (2.5 + 1e-7, false)] {
+            let ray = Ray::new(Vec3::new(-5.0, y, 0.0), Vec3::new(1.0, 0.0, 0.0));
+            assert_eq!(torus.hit(&ray, 0.0, f64::INFINITY).is_some(), hits);
+        }
+
+        // rays grazing the inner equator from the hole
+        let ray = Ray::new(Vec3::new(-5.0, 1.5 + 1e-7, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let record = torus.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!((record.hit_at.x() + (2.5f64.powi(2) - 1.5f64.powi(2)).sqrt()).abs() < 1e-6);
+
+        // hits of random rays nearly tangent to the tube lie on the surface
+        let mut rng = rand::thread_rng();
+        for _ in 0..1000 {
+            let target = torus_point(&mut rng);
+            let normal = (target - core_point(target)).normalized();
+            let tangent = normal.cross(Sphere::unit().random_point_on_surface(&mut rng));
+            let origin = target + 3.0 * tangent.normalized() + 1e-6 * normal;
+            let ray = Ray::new(origin, target - 1e-6 * normal - origin);
+
+            let record = torus.hit(&ray, 0.0, f64::INFINITY).unwrap();
+            let distance = (record.hit_at - core_point(record.hit_at)).norm();
+            assert!((distance - 0.5).abs() < 1e-9, "{}", distance);
+            assert!(aabb.hit(&ray, 0.0, f64::INFINITY));
+        }
+    }
+
+    /// A random point on the torus of [ring](ring).
+    fn torus_point<R: rand::Rng>(rng: &mut R) -> Vec3 {
+        let (phi, theta) = (rng.gen_range(0.0..2.0 * PI), rng.gen_range(0.0..2.0 * PI));
+        let rho = 2.0 + 0.5 * theta.cos();
+        Vec3::new(rho * phi.cos(), rho * phi.sin(), 0.5 * theta.sin())
+    }
+
+    /// The closest point to p on the circle through the tube of [ring](ring).
+    fn core_point(p: Vec3) -> Vec3 {
+        Vec3::new(p.x(), p.y(), 0.0).stretch(2.0)
+    }
+}
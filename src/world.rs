@@ -1,16 +1,21 @@
 use rand::Rng;
 
 use crate::{
+    environment::Background,
     hittable::{HitRecord, Hittable, HittableObject, AABB},
+    light::Light,
     material::{Material, Scatter},
     ray::Ray,
+    texture::Texture,
     Error, NonNan, Vec3,
 };
 
 /// The result of a ray hitting the world.
-pub struct HitEvent {
+pub struct HitEvent<'a> {
     /// When, where and how a ray hit an object.
     pub record: HitRecord,
+    /// The material of the object hit by the ray.
+    pub material: &'a Material,
     /// Whether and how the ray scattered after the hit.
     pub scatter: Option<Scatter>,
 }
@@ -18,7 +23,9 @@ pub struct HitEvent {
 /// Builder of [World], a collection of hittable objects.
 #[derive(Default)]
 pub struct WorldBuilder {
-    objects: Vec<(HittableObject, Material)>,
+    objects: Vec<(HittableObject, Material, Option<Texture>)>,
+    lights: Vec<Light>,
+    background: Background,
 }
 
 impl WorldBuilder {
@@ -33,7 +40,33 @@ impl WorldBuilder {
         O: Into<HittableObject>,
         M: Into<Material>,
     {
-        self.objects.push((obj.into(), material.into()))
+        self.objects.push((obj.into(), material.into(), None))
+    }
+
+    /// Add an hittable object with an opacity texture to the world, e.g. a leaf modeled by a quad.
+    /// Rays pass through the object where the opacity is zero and are stopped where it's one,
+    /// partially opaque parts stop rays randomly with the opacity as the probability.
+    pub fn add_masked<O, M, T>(&mut self, obj: O, material: M, opacity: T)
+    where
+        O: Into<HittableObject>,
+        M: Into<Material>,
+        T: Into<Texture>,
+    {
+        self.objects
+            .push((obj.into(), material.into(), Some(opacity.into())))
+    }
+
+    /// Add a light source without geometry to the world.
+    pub fn add_light<L: Into<Light>>(&mut self, light: L) {
+        self.lights.push(light.into())
+    }
+
+    /// Set what rays escaping the world see.
+    ///
+    /// # Default:
+    /// [Background::Gradient](Background::Gradient)
+    pub fn background<B: Into<Background>>(&mut self, background: B) {
+        self.background = background.into();
     }
 
     /// Build a world with efficient hit detection.
@@ -41,7 +74,11 @@ impl WorldBuilder {
         let mut nodes: Vec<_> = self
             .objects
             .into_iter()
-            .map(|(object, material)| BVH::Leaf { object, material })
+            .map(|(object, material, opacity)| BVH::Leaf {
+                object,
+                material,
+                opacity,
+            })
             .collect();
         let mut rng = rand::thread_rng();
 
@@ -80,20 +117,51 @@ impl WorldBuilder {
 
         Ok(World {
             bvh: nodes.swap_remove(0),
+            lights: self.lights,
+            background: self.background,
         })
     }
 }
 
-/// A collection of hittable objects. Support more efficient hit detection than a simple vector of
-/// objects and materials.
+/// A collection of hittable objects and light sources. Support more efficient hit detection than a
+/// simple vector of objects and materials.
 pub struct World {
     bvh: BVH,
+    lights: Vec<Light>,
+    background: Background,
 }
 
 impl World {
     /// Hit the world with a ray.
-    pub fn hit<R: Rng>(&self, rng: &mut R, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitEvent> {
-        self.bvh.hit(rng, ray, t_min, t_max)
+    pub fn hit<R: Rng>(
+        &self,
+        rng: &mut R,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<HitEvent<'_>> {
+        self.bvh
+            .hit(rng, ray, t_min, t_max)
+            .map(|(record, material)| HitEvent {
+                scatter: material.scatter(rng, ray, &record),
+                material,
+                record,
+            })
+    }
+
+    /// Return whether any object blocks the ray within the given range of ray parameter.
+    pub fn occluded<R: Rng>(&self, rng: &mut R, ray: &Ray, t_min: f64, t_max: f64) -> bool {
+        self.bvh.hit(rng, ray, t_min, t_max).is_some()
+    }
+
+    /// Light sources without geometry in the world.
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+    /// What rays escaping the world see.
+    pub fn background(&self) -> &Background {
+        &self.background
     }
 }
 
@@ -101,6 +169,7 @@ enum BVH {
     Leaf {
         object: HittableObject,
         material: Material,
+        opacity: Option<Texture>,
     },
     Node {
         aabb: AABB,
@@ -117,20 +186,45 @@ impl BVH {
         }
     }
 
-    fn hit<R: Rng>(&self, rng: &mut R, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitEvent> {
+    fn hit<R: Rng>(
+        &self,
+        rng: &mut R,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<(HitRecord, &Material)> {
         match self {
-            BVH::Leaf { object, material } => {
-                object.hit(ray, t_min, t_max).map(|record| HitEvent {
-                    scatter: material.scatter(rng, ray, &record),
-                    record,
-                })
+            BVH::Leaf {
+                object,
+                material,
+                opacity,
+            } => {
+                let mut t_min = t_min;
+
+                loop {
+                    let record = object.hit(ray, t_min, t_max)?;
+                    let opaque = match opacity {
+                        Some(opacity) => {
+                            let alpha = opacity.scalar(record.u, record.v);
+                            alpha >= 1.0 || (alpha > 0.0 && rng.gen::<f64>() < alpha)
+                        }
+                        None => true,
+                    };
+
+                    if opaque {
+                        return Some((record, material));
+                    }
+
+                    // the ray passes through the masked hit, look for the next hit behind it
+                    t_min = record.t + 1e-9 * record.t.abs().max(1.0);
+                }
             }
             BVH::Node { aabb, left, right } => {
                 if !aabb.hit(ray, t_min, t_max) {
                     None
                 } else {
                     let hit_left = left.hit(rng, ray, t_min, t_max);
-                    let t = hit_left.as_ref().map(|rec| rec.record.t).unwrap_or(t_max);
+                    let t = hit_left.as_ref().map(|(rec, _)| rec.t).unwrap_or(t_max);
                     let hit_right = right.hit(rng, ray, t_min, t);
 
                     // if the ray right subtree, the hit is closer to the source of the ray than the
@@ -141,3 +235,42 @@ impl BVH {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{color::Rgb, hittable::Quad, material::Lambertian, texture::Checker};
+
+    #[test]
+    fn alpha_masked_quad() {
+        let mut rng = rand::thread_rng();
+        let quad = || {
+            Quad::new(
+                Vec3::new(-1.0, -1.0, 0.0),
+                Vec3::new(2.0, 0.0, 0.0),
+                Vec3::new(0.0, 2.0, 0.0),
+            )
+        };
+        let material = || Lambertian::new(Rgb::new(0.5, 0.5, 0.5));
+
+        let mut builder = WorldBuilder::new();
+        builder.add_masked(quad(), material(), Checker::new(0.0, 1.0, 2.0));
+        let cutout = builder.build().unwrap();
+
+        // the quad is cut out in the upper left and the lower right quarter
+        let ray = |x: f64, y: f64| Ray::new(Vec3::new(x, y, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(cutout.occluded(&mut rng, &ray(-0.5, -0.5), 0.0, f64::INFINITY));
+        assert!(!cutout.occluded(&mut rng, &ray(-0.5, 0.5), 0.0, f64::INFINITY));
+
+        let mut builder = WorldBuilder::new();
+        builder.add_masked(quad(), material(), 0.25);
+        let translucent = builder.build().unwrap();
+
+        const N: usize = 10_000;
+        let hits = (0..N)
+            .filter(|_| translucent.occluded(&mut rng, &ray(0.0, 0.0), 0.0, f64::INFINITY))
+            .count();
+        let fraction = hits as f64 / N as f64;
+        assert!((fraction - 0.25).abs() < 0.03, "{}", fraction);
+    }
+}